@@ -0,0 +1,109 @@
+//! Compares the brute force rolling sum detection against the summed-area table.
+//!
+//! Run with `cargo bench --bench detection`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use shellshock_tracer::{Coordinate, Rect, Size};
+use shellshock_tracer::bitmap::{Bitmap, BitmapBuf, ARGB};
+use shellshock_tracer::image_processing::{
+    find_tank, tank_size_for_dimensions, FrameScores, SummedAreaTable, Team, MENU_BAR
+};
+use shellshock_tracer::viewport::Layout;
+
+const DIMENSIONS: Size<u32> = Size(2560, 1440);
+const ITERATIONS: u32 = 5;
+/// The coarse step the brute force search used before refining
+const OVERLAP_PIXELS: usize = 50;
+
+fn time<F: FnMut()>(name: &str, mut f: F) {
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        f();
+        total += start.elapsed();
+    }
+    println!("{name:<44} {:>10.2?} per frame", total / ITERATIONS);
+}
+
+/// Sums every `window_size` window between `from` and `to` directly, stepping by `overlap` pixels.
+/// Returns the coordinate of the highest scoring window, along with its score.
+///
+/// This is O(windows * window area), which is what the summed-area table replaced.
+fn rolling_sum_bitmap(bitmap: &Bitmap<f32>, from: Coordinate<usize>, to: Coordinate<usize>, window_size: Size<usize>, overlap: usize) -> Option<(Coordinate<usize>, f32)> {
+    let game_dimensions = (to.0.checked_sub(from.0)?, to.1.checked_sub(from.1)?);
+    let mut highest_rect = (f32::MIN, None);
+
+    for col_count in 0..=(game_dimensions.0.checked_sub(window_size.0)? / overlap) {
+        let pixel_col = col_count*overlap + from.0;
+
+        for row_count in 0..=(game_dimensions.1.checked_sub(window_size.1)? / overlap) {
+            let pixel_row = row_count*overlap + from.1;
+
+            let rows = bitmap.subrect(Coordinate(pixel_col, pixel_row), Size(window_size.0, window_size.1));
+            let score = rows.fold(0.0, |acc, row| acc + row.iter().sum::<f32>());
+
+            if score > highest_rect.0 {
+                highest_rect = (score, Some(Coordinate(pixel_col, pixel_row)))
+            }
+        }
+    }
+
+    highest_rect.1.map(|coord| (coord, highest_rect.0))
+}
+
+fn main() {
+    let mut screen = BitmapBuf::new(DIMENSIONS, ARGB { r: 60, g: 80, b: 120, a: 255 });
+    let width = screen.width();
+    screen.as_bitmap().fill_with(|i| {
+        let (x, y) = (i % width, i / width);
+        if (1200..1244).contains(&x) && (700..728).contains(&y) {
+            ARGB { r: 20, g: 220, b: 30, a: 255 }
+        } else {
+            ARGB { r: 60, g: 80 + (x % 40) as u8, b: 120, a: 255 }
+        }
+    });
+
+    let mut scores = BitmapBuf::new(DIMENSIONS, 0.0);
+    scores.as_bitmap().fill_with(|i| Team::Player.pixel_score(screen.pixels()[i]));
+
+    let dimensions = Size(DIMENSIONS.0 as usize, DIMENSIONS.1 as usize);
+    let tank_size = tank_size_for_dimensions(dimensions, Layout::default());
+    let menu_size_pixels = (dimensions.1 as f32 * MENU_BAR) as usize;
+    let (from, to) = (Coordinate(0, menu_size_pixels), Coordinate(dimensions.0, dimensions.1));
+    let play_area = Rect { origin: from, size: Size(to.0 - from.0, to.1 - from.1) };
+
+    time("rolling sum, coarse then refined", || {
+        let score_bitmap = scores.as_bitmap();
+        let (coarse, _) = rolling_sum_bitmap(&score_bitmap, from, to, tank_size, OVERLAP_PIXELS).unwrap();
+        let expanded_from = Coordinate(coarse.0.saturating_sub(tank_size.0), coarse.1.saturating_sub(tank_size.1));
+        let expanded_to = Coordinate(
+            dimensions.0.min(expanded_from.0 + 3*tank_size.0),
+            dimensions.1.min(expanded_from.1 + 3*tank_size.1)
+        );
+        black_box(rolling_sum_bitmap(&score_bitmap, expanded_from, expanded_to, tank_size, 1));
+    });
+
+    time("summed-area table, full resolution", || {
+        let table = SummedAreaTable::new(&scores.as_bitmap());
+        black_box(table.peaks(play_area, tank_size, 1));
+    });
+
+    // a brute force search at full resolution over the whole screen takes minutes, so compare on a smaller region
+    let (region_from, region_to) = (Coordinate(1000, 600), Coordinate(1400, 900));
+    let region = Rect { origin: region_from, size: Size(400, 300) };
+
+    time("rolling sum, full resolution 400x300", || {
+        black_box(rolling_sum_bitmap(&scores.as_bitmap(), region_from, region_to, tank_size, 1));
+    });
+
+    time("summed-area table, full resolution 400x300", || {
+        let table = SummedAreaTable::new(&scores.as_bitmap());
+        black_box(table.peaks(region, tank_size, 1));
+    });
+
+    time("find_tank (scoring and table)", || {
+        let _ = black_box(find_tank(&FrameScores::new(&screen.as_bitmap()), Layout::default()));
+    });
+}
@@ -0,0 +1,53 @@
+use std::error::Error;
+
+use crate::{Coordinate, Size};
+use crate::bitmap::{Bitmap, ARGB};
+use crate::draw::TrajectoryStyle;
+use crate::font::TextStyle;
+use crate::impact::ImpactMarker;
+use crate::trajectory::Trajectory;
+use crate::tracking::TrackingStatus;
+
+/// A source of captured game frames.
+pub trait FrameSource {
+    type Error: Error + 'static;
+
+    /// The dimensions of the captured frames.
+    fn dimensions(&self) -> Size<u32>;
+
+    /// Capture the current frame into `buffer`.
+    ///
+    /// The buffer must have the dimensions returned by `dimensions`.
+    /// Rows are stored bottom-up, so coordinates are relative to the bottom-left.
+    fn capture(&mut self, buffer: &mut Bitmap<ARGB>) -> Result<(), Self::Error>;
+}
+
+/// A surface the tracer overlay is drawn onto and presented from.
+pub trait OverlaySink {
+    type Error: Error + 'static;
+
+    /// Draw `trajectory` onto the overlay as a dotted line.
+    fn draw_trajectory(&mut self, trajectory: &Trajectory, style: TrajectoryStyle) -> Result<(), Self::Error>;
+
+    /// Draw where a shot lands, the extent of its blast and the tanks inside it.
+    fn draw_impact(&mut self, marker: &ImpactMarker) -> Result<(), Self::Error>;
+
+    /// Draw `text` onto the overlay with the bottom-left corner of the block at `position`.
+    fn draw_text(&mut self, text: &str, position: Coordinate<i32>, style: TextStyle) -> Result<(), Self::Error>;
+
+    /// Draw an indicator of whether the tank is currently found, held at its last position or lost.
+    fn draw_status(&mut self, status: &TrackingStatus) -> Result<(), Self::Error>;
+
+    /// Present the overlay bitmap to the screen.
+    fn present(&mut self) -> Result<(), Self::Error>;
+
+    /// Clear the overlay bitmap, ready for the next frame.
+    fn clear(&mut self) -> Result<(), Self::Error>;
+
+    /// Process any pending events for the overlay.
+    ///
+    /// Returns `false` once the overlay has been closed and the event loop should stop.
+    fn handle_events(&mut self) -> bool {
+        true
+    }
+}
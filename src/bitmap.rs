@@ -1,7 +1,9 @@
-use std::io;
-use std::fs::write;
+use std::path::Path;
+
+use thiserror::Error;
 
 use crate::{Coordinate, Size};
+use crate::codec::{load_image, save_image, CodecError};
 
 // will never be used as windows is little endian
 #[repr(C)]
@@ -77,7 +79,7 @@ impl<'a, T> Iterator for BitmapSubrectRows<'a, T> {
     type Item = &'a [T];
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_row >= self.size.0 {
+        if self.current_row >= self.size.1 {
             return None
         }
 
@@ -86,7 +88,7 @@ impl<'a, T> Iterator for BitmapSubrectRows<'a, T> {
         let start_index = index_of_row+self.start_coord.0;
         self.current_row += 1;
 
-        Some(&self.inner[start_index..start_index+self.size.1])
+        Some(&self.inner[start_index..start_index+self.size.0])
     }
 }
 
@@ -104,6 +106,10 @@ impl<'a, T> Bitmap<'a, T> {
         self.inner.len() / self.width
     }
 
+    pub fn dimensions(&self) -> Size<u32> {
+        Size(self.width as u32, self.height() as u32)
+    }
+
     /// The function takes a single usize, the index of the pixel in the inner slice.
     pub fn fill_with<F: FnMut(usize) -> T>(&mut self, mut f: F) {
         for (i, pixel) in self.inner.iter_mut().enumerate() {
@@ -116,10 +122,26 @@ impl<'a, T> Bitmap<'a, T> {
     }
 }
 
-impl<'a, ARGB> Bitmap<'a, ARGB> {
-    /// ONLY FOR DEBUGGING
-    pub fn save_raw(&self, path: &str) -> io::Result<()> {
-        write::<_, &[u8]>(path, unsafe { std::slice::from_raw_parts(self.inner.as_ptr().cast(), self.inner.len()*4) })
+impl<'a> Bitmap<'a, ARGB> {
+    /// Save the bitmap as an image, with the format chosen from the file extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CodecError> {
+        save_image(path, self.inner, self.dimensions())
+    }
+}
+
+impl<'a> Bitmap<'a, f32> {
+    /// Returns the bitmap as greyscale pixels, scaled so the highest value is white and
+    /// anything at or below zero is black. Useful for inspecting score bitmaps.
+    pub fn to_greyscale(&self) -> Vec<ARGB> {
+        let max = self.inner.iter().copied().fold(0.0, f32::max);
+        let scale = if max > 0.0 { 255.0 / max } else { 0.0 };
+
+        self.inner.iter()
+            .map(|value| {
+                let grey = (value.max(0.0) * scale) as u8;
+                ARGB { r: grey, g: grey, b: grey, a: 255 }
+            })
+            .collect()
     }
 }
 
@@ -128,16 +150,102 @@ impl<'a, T: Clone> Bitmap<'a, T> {
         self.inner.fill(value)
     }
 
-    /// Returns a `Bitmap` with a static lifetime.
-    /// 
-    /// Dropping this bitmap will not deallocate the inner data.
-    /// 
-    /// It is backed by a slice with capacity for `dimensions.0*dimensions.1` of type `T`.
-    pub fn new_static(dimensions: Size<u32>, fill: T) -> Bitmap<'static, T> {
+    /// Copy the bitmap into an owned `BitmapBuf`.
+    pub fn to_bitmap_buf(&self) -> BitmapBuf<T> {
+        BitmapBuf { inner: self.inner.to_vec(), width: self.width }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BitmapError {
+    #[error("A bitmap's width can't be zero")]
+    ZeroWidth,
+    #[error("The bitmap length {length} is not a multiple of its width {width}")]
+    LengthMismatch { length: usize, width: usize }
+}
+
+/// An owned bitmap. `Bitmap` views of it can be borrowed with `as_bitmap`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitmapBuf<T> {
+    inner: Vec<T>,
+    width: usize
+}
+
+impl<T> BitmapBuf<T> {
+    /// The length of `inner` must be a multiple of `width`, which can't be zero.
+    pub fn from_vec(inner: Vec<T>, width: usize) -> Result<Self, BitmapError> {
+        if width == 0 {
+            return Err(BitmapError::ZeroWidth)
+        }
+        if !inner.len().is_multiple_of(width) {
+            return Err(BitmapError::LengthMismatch { length: inner.len(), width })
+        }
+
+        Ok(Self { inner, width })
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.inner
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Zero if the bitmap was created with a width of zero.
+    pub fn height(&self) -> usize {
+        self.inner.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    pub fn dimensions(&self) -> Size<u32> {
+        Size(self.width as u32, self.height() as u32)
+    }
+
+    pub fn pixels(&self) -> &[T] {
+        &self.inner
+    }
+
+    pub fn as_bitmap(&mut self) -> Bitmap<'_, T> {
+        Bitmap { inner: &mut self.inner, width: self.width }
+    }
+}
+
+impl<T: Clone> BitmapBuf<T> {
+    /// Create a bitmap of `dimensions.0*dimensions.1` pixels, all set to `fill`.
+    pub fn new(dimensions: Size<u32>, fill: T) -> Self {
+        let length = (dimensions.0*dimensions.1) as usize;
+        Self { inner: vec![fill; length], width: dimensions.0 as usize }
+    }
+
+    /// Resize the bitmap, setting every pixel to `fill`.
+    ///
+    /// None of the existing pixels are kept, even if the dimensions are unchanged,
+    /// only the allocation is reused where possible.
+    pub fn resize(&mut self, dimensions: Size<u32>, fill: T) {
         let length = (dimensions.0*dimensions.1) as usize;
-        let inner = vec![fill; length];
-        let slice = inner.leak();
-        Bitmap { inner: slice, width: dimensions.0 as usize }
+        self.inner.clear();
+        self.inner.resize(length, fill);
+        self.width = dimensions.0 as usize;
+    }
+}
+
+impl<'a, T> From<&'a mut BitmapBuf<T>> for Bitmap<'a, T> {
+    fn from(value: &'a mut BitmapBuf<T>) -> Self {
+        value.as_bitmap()
+    }
+}
+
+impl BitmapBuf<ARGB> {
+    /// Load an image, with the format detected from the file's contents.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CodecError> {
+        let (pixels, dimensions) = load_image(path)?;
+        Self::from_vec(pixels, dimensions.0 as usize)
+            .map_err(|_| CodecError::InvalidData("pixel count doesn't match the image dimensions"))
+    }
+
+    /// Save the bitmap as an image, with the format chosen from the file extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CodecError> {
+        save_image(path, &self.inner, self.dimensions())
     }
 }
 
@@ -148,6 +256,7 @@ mod tests {
     use std::mem;
 
     #[test]
+    #[allow(clippy::needless_range_loop)]
     fn subrect_test() {
         let mut test_image = [ARGB {a: 255, r: 50, b: 50, g: 50}; 100];
         for i in 0..50 {
@@ -175,12 +284,62 @@ mod tests {
         assert_eq!(expected.as_slice(), pixels.as_slice());
     }
 
+    #[test]
+    fn subrect_non_square_test() {
+        let mut values: Vec<u32> = (0..20).collect();
+        let bitmap = Bitmap::new(&mut values, 5);
+
+        let rows: Vec<&[u32]> = bitmap.subrect(Coordinate(1, 1), Size(3, 2)).collect();
+        assert_eq!(rows, [[6, 7, 8].as_slice(), &[11, 12, 13]]);
+    }
+
     #[test]
     fn pre_mult_test() {
         let rgba = ARGB {r: 100, g: 100, b: 100, a: 50};
         assert_eq!(0x32131313_u32, rgba.as_premult_alpha().into());
     }
 
+    #[test]
+    fn greyscale_test() {
+        let mut scores = [-5.0, 0.0, 50.0, 100.0];
+        let bitmap = Bitmap::new(scores.as_mut_slice(), 2);
+
+        let greys: Vec<u8> = bitmap.to_greyscale().iter().map(|pixel| pixel.g).collect();
+        assert_eq!(greys, [0, 0, 127, 255]);
+    }
+
+    #[test]
+    fn bitmap_buf_test() {
+        let mut buf = BitmapBuf::new(Size(4, 2), 1u8);
+        buf.as_bitmap().inner[5] = 7;
+
+        assert_eq!(buf.height(), 2);
+        assert_eq!(buf.as_bitmap().subrect(Coordinate(1, 1), Size(1, 1)).next(), Some([7].as_slice()));
+
+        let copy = buf.as_bitmap().to_bitmap_buf();
+        assert_eq!(copy, buf);
+
+        buf.resize(Size(3, 3), 0);
+        assert_eq!(buf.dimensions(), Size(3, 3));
+        assert!(buf.pixels().iter().all(|pixel| *pixel == 0));
+
+        // resizing to the same dimensions still clears every pixel
+        buf.as_bitmap().inner[4] = 9;
+        buf.resize(Size(3, 3), 2);
+        assert_eq!(buf.pixels(), [2; 9]);
+
+        assert_eq!(copy.into_vec(), [1, 1, 1, 1, 1, 7, 1, 1]);
+    }
+
+    #[test]
+    fn bitmap_buf_from_vec_test() {
+        assert_eq!(BitmapBuf::from_vec(vec![1, 2, 3, 4], 2).map(|buf| buf.dimensions()), Ok(Size(2, 2)));
+        assert_eq!(BitmapBuf::from_vec(vec![1, 2, 3], 0), Err(BitmapError::ZeroWidth));
+        assert_eq!(BitmapBuf::from_vec(vec![1, 2, 3], 2), Err(BitmapError::LengthMismatch { length: 3, width: 2 }));
+
+        assert_eq!(BitmapBuf::new(Size(0, 5), 0u8).dimensions(), Size(0, 0));
+    }
+
     #[test]
     fn layout_test() {
         assert_eq!(mem::size_of::<ARGB>(), 4);
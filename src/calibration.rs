@@ -0,0 +1,335 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{Rect, Size};
+use crate::backend::FrameSource;
+use crate::bitmap::{Bitmap, BitmapBuf, ARGB};
+use crate::settings::{PhysicsSection, SettingsError};
+use crate::tank::{Physics, Tank};
+use crate::viewport::{Layout, Viewport};
+
+/// The time between frames captured while recording a shot.
+pub const RECORD_INTERVAL: Duration = Duration::from_millis(20);
+/// The fewest observations of a shot that can be calibrated from.
+pub const MIN_OBSERVATIONS: usize = 5;
+/// The largest summed channel difference between frames that isn't considered movement.
+const CHANGE_THRESHOLD: u32 = 60;
+/// Frames where more than this fraction of the screen changed are ignored, as something other than the projectile moved.
+const MAX_CHANGED_FRACTION: f32 = 0.01;
+
+#[derive(Error, Debug)]
+pub enum CalibrationError {
+    #[error("Could not read or write the calibration profile: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid calibration profile: {0}")]
+    InvalidProfile(#[from] SettingsError),
+    #[error("Only {found} observations of the shot were recorded, at least {MIN_OBSERVATIONS} are needed")]
+    TooFewObservations { found: usize },
+    #[error("The recorded shot can't be calibrated from: {0}")]
+    Degenerate(&'static str)
+}
+
+/// The position of the projectile at a point in time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Observation {
+    /// Seconds since recording started
+    pub time: f32,
+    /// Relative to bottom left
+    pub position: (f32, f32)
+}
+
+/// Records the path of a projectile by finding what moved between consecutive frames.
+pub struct ShotRecorder {
+    /// Areas where movement isn't the projectile, such as around the firing tank
+    pub ignore: Vec<Rect>,
+    /// Where the menu bar is, as movement on it is ignored
+    pub layout: Layout,
+    previous: Option<(BitmapBuf<ARGB>, f32)>,
+    observations: Vec<Observation>
+}
+
+fn colour_distance(a: ARGB, b: ARGB) -> u32 {
+    a.r.abs_diff(b.r) as u32 + a.g.abs_diff(b.g) as u32 + a.b.abs_diff(b.b) as u32
+}
+
+impl ShotRecorder {
+    pub fn new(ignore: Vec<Rect>, layout: Layout) -> Self {
+        Self { ignore, layout, previous: None, observations: Vec::new() }
+    }
+
+    pub fn observations(&self) -> &[Observation] {
+        &self.observations
+    }
+
+    /// Compare a frame captured `time` seconds after recording started with the previous frame.
+    ///
+    /// The centre of the pixels that changed lies between where the projectile was in each frame,
+    /// so is observed at the midpoint of their times.
+    pub fn record(&mut self, frame: &Bitmap<ARGB>, time: f32) {
+        let (width, height) = (frame.width, frame.height());
+        let menu_top = Viewport::new(Size(width, height), self.layout).menu_bar_top();
+
+        if let Some((previous, previous_time)) = &self.previous {
+            let mut changed = 0;
+            let mut sum = (0.0, 0.0);
+
+            for y in menu_top..height {
+                for x in 0..width {
+                    let i = y*width + x;
+                    let ignored = self.ignore.iter().any(|rect| (rect.origin.0..rect.end().0).contains(&x) && (rect.origin.1..rect.end().1).contains(&y));
+
+                    if !ignored && colour_distance(frame.inner[i], previous.pixels()[i]) > CHANGE_THRESHOLD {
+                        changed += 1;
+                        sum = (sum.0 + x as f32, sum.1 + y as f32);
+                    }
+                }
+            }
+
+            let max_changed = (width * height) as f32 * MAX_CHANGED_FRACTION;
+            if changed > 0 && changed as f32 <= max_changed {
+                let position = (sum.0 / changed as f32, sum.1 / changed as f32);
+                self.observations.push(Observation { time: (previous_time + time) / 2.0, position });
+            }
+        }
+
+        self.previous = Some((frame.to_bitmap_buf(), time));
+    }
+}
+
+/// Capture frames for `duration`, recording the path of anything that moves outside `ignore`.
+pub fn record_shot<S: FrameSource>(frame_source: &mut S, ignore: Vec<Rect>, layout: Layout, duration: Duration) -> Result<Vec<Observation>, S::Error> {
+    let mut recorder = ShotRecorder::new(ignore, layout);
+    let mut buffer = BitmapBuf::new(frame_source.dimensions(), 0.into());
+    let start = Instant::now();
+
+    while start.elapsed() < duration {
+        frame_source.capture(&mut buffer.as_bitmap())?;
+        recorder.record(&buffer.as_bitmap(), start.elapsed().as_secs_f32());
+        thread::sleep(RECORD_INTERVAL);
+    }
+
+    Ok(recorder.observations)
+}
+
+/// Fit `a + b*t + c*t^2` to the samples by least squares, returning `[a, b, c]`.
+fn fit_quadratic(samples: impl Iterator<Item = (f32, f32)>) -> Option<[f64; 3]> {
+    // the normal equations, solved by Cramer's rule
+    let mut powers = [0.0f64; 5];
+    let mut rhs = [0.0f64; 3];
+    for (t, value) in samples {
+        let (t, value) = (t as f64, value as f64);
+        for (power, sum) in powers.iter_mut().enumerate() {
+            *sum += t.powi(power as i32);
+        }
+        for (power, sum) in rhs.iter_mut().enumerate() {
+            *sum += value * t.powi(power as i32);
+        }
+    }
+
+    let matrix = [
+        [powers[0], powers[1], powers[2]],
+        [powers[1], powers[2], powers[3]],
+        [powers[2], powers[3], powers[4]]
+    ];
+    let determinant = |m: [[f64; 3]; 3]| {
+        m[0][0]*(m[1][1]*m[2][2] - m[1][2]*m[2][1])
+            - m[0][1]*(m[1][0]*m[2][2] - m[1][2]*m[2][0])
+            + m[0][2]*(m[1][0]*m[2][1] - m[1][1]*m[2][0])
+    };
+
+    let full = determinant(matrix);
+    if full.abs() < f64::EPSILON {
+        return None
+    }
+
+    let mut coefficients = [0.0; 3];
+    for (column, coefficient) in coefficients.iter_mut().enumerate() {
+        let mut replaced = matrix;
+        for row in 0..3 {
+            replaced[row][column] = rhs[row];
+        }
+        *coefficient = determinant(replaced) / full;
+    }
+
+    Some(coefficients)
+}
+
+/// Fit the physics constants to the observed path of a shot fired by `tank`.
+///
+/// Each axis of the path is fitted with a quadratic in time. The launch is where the path passes the tank's height,
+/// and comparing the launch velocity with the tank's power and angle gives how quickly the curve's `t` passes.
+/// The wind constant can only be fitted if there was wind, otherwise the tank's current value is kept.
+pub fn calibrate(tank: &Tank, viewport: &Viewport, observations: &[Observation]) -> Result<Physics, CalibrationError> {
+    if observations.len() < MIN_OBSERVATIONS {
+        return Err(CalibrationError::TooFewObservations { found: observations.len() })
+    }
+
+    let [_, x1, x2] = fit_quadratic(observations.iter().map(|o| (o.time, o.position.0)))
+        .ok_or(CalibrationError::Degenerate("the observations are too close together in time"))?;
+    let [y0, y1, y2] = fit_quadratic(observations.iter().map(|o| (o.time, o.position.1)))
+        .ok_or(CalibrationError::Degenerate("the observations are too close together in time"))?;
+
+    if y2 >= 0.0 {
+        return Err(CalibrationError::Degenerate("the shot doesn't fall"))
+    }
+
+    // the earlier time the path is at the tank's height
+    let launch_height = tank.screen_position.1 as f64;
+    let discriminant = y1*y1 - 4.0*y2*(y0 - launch_height);
+    if discriminant < 0.0 {
+        return Err(CalibrationError::Degenerate("the shot never passes the tank's height"))
+    }
+    let launch = (-y1 + discriminant.sqrt()) / (2.0*y2);
+
+    let velocity = (x1 + 2.0*x2*launch, y1 + 2.0*y2*launch);
+
+    let (x_scale_ratio, y_scale_ratio) = viewport.scale();
+    let (x_scale_ratio, y_scale_ratio) = (x_scale_ratio as f64, y_scale_ratio as f64);
+    let direction = tank.direction.as_float_multiplier() as f64;
+    let angle = (tank.angle as f64).to_radians();
+
+    // the launch velocity per unit of t
+    let unit_velocity = (
+        direction * x_scale_ratio * tank.power as f64 * angle.cos(),
+        y_scale_ratio * tank.power as f64 * angle.sin()
+    );
+    let time_scale = (velocity.0*unit_velocity.0 + velocity.1*unit_velocity.1) / (unit_velocity.0.powi(2) + unit_velocity.1.powi(2));
+    if !time_scale.is_finite() || time_scale <= 0.0 {
+        return Err(CalibrationError::Degenerate("the shot doesn't move in the direction it was fired"))
+    }
+
+    let path_constant = -2.0*y2 / (y_scale_ratio * time_scale.powi(2));
+    let wind_constant = if tank.wind == 0 {
+        tank.physics.wind_constant
+    } else {
+        (2.0*x2 / (direction * tank.wind as f64 * x_scale_ratio * time_scale.powi(2))) as f32
+    };
+    if !(wind_constant.is_finite() && wind_constant >= 0.0) {
+        return Err(CalibrationError::Degenerate("the shot drifts against the wind"))
+    }
+
+    // checked the same way as a profile, so the result can always be saved and loaded again
+    let physics = PhysicsSection { wind_constant: Some(wind_constant), path_constant: Some(path_constant as f32) };
+    Ok(physics.to_physics(tank.physics)?)
+}
+
+/// The layout of a calibration profile, the `[physics]` section of the settings file.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    physics: PhysicsSection
+}
+
+/// Parse a calibration profile, checked the same way as the `[physics]` section of the settings file.
+pub fn parse_profile(profile: &str) -> Result<Physics, CalibrationError> {
+    let file: ProfileFile = toml::from_str(profile).map_err(SettingsError::from)?;
+
+    if file.physics.wind_constant.is_none() {
+        return Err(SettingsError::Invalid { key: "physics.wind_constant", reason: "is required" }.into())
+    }
+    if file.physics.path_constant.is_none() {
+        return Err(SettingsError::Invalid { key: "physics.path_constant", reason: "is required" }.into())
+    }
+
+    Ok(file.physics.to_physics(Physics::default())?)
+}
+
+pub fn format_profile(physics: &Physics) -> String {
+    let file = ProfileFile {
+        physics: PhysicsSection { wind_constant: Some(physics.wind_constant), path_constant: Some(physics.path_constant) }
+    };
+    let table = toml::to_string(&file).expect("a profile only contains numbers");
+    format!("# shellshock-tracer calibration profile, for a 2560x1440 screen\n{table}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Coordinate;
+    use crate::tank::Direction;
+
+    const DIMENSIONS: Size<u32> = Size(1280, 720);
+
+    fn viewport() -> Viewport {
+        Viewport::from_dimensions(DIMENSIONS, Layout::default())
+    }
+
+    fn tank(physics: Physics) -> Tank {
+        Tank { screen_position: Coordinate(900, 250), physics, ..Tank::new(70, 50, 20, Direction::Left) }
+    }
+
+    /// Observations of a shot fired 0.3 seconds into the recording, where t passes 25 times a second.
+    fn observations(physics: Physics) -> Vec<Observation> {
+        let shot = tank(physics);
+        (7..40).map(|frame| {
+            let time = frame as f32 * 0.05;
+            let (x, y) = shot.curve_offset(25.0 * (time - 0.3), &viewport());
+            Observation { time, position: (x + 900.0, y + 250.0) }
+        }).collect()
+    }
+
+    #[test]
+    fn calibrate_test() {
+        let actual = Physics { wind_constant: 0.005, path_constant: 3.5 };
+
+        let fitted = calibrate(&tank(Physics::default()), &viewport(), &observations(actual)).unwrap();
+
+        assert!((fitted.wind_constant - actual.wind_constant).abs() < 1e-4, "{fitted:?}");
+        assert!((fitted.path_constant - actual.path_constant).abs() < 1e-2, "{fitted:?}");
+
+        let result = calibrate(&tank(Physics::default()), &viewport(), &observations(actual)[..3]);
+        assert!(matches!(result, Err(CalibrationError::TooFewObservations { found: 3 })));
+
+        // a shot pushed against the wind would fit a negative wind constant, which can't be saved
+        let against_wind = observations(Physics { wind_constant: -0.005, ..actual });
+        let result = calibrate(&tank(Physics::default()), &viewport(), &against_wind);
+        assert!(matches!(result, Err(CalibrationError::Degenerate(_))), "{result:?}");
+    }
+
+    #[test]
+    fn shot_recorder_test() {
+        let background = ARGB { r: 100, g: 150, b: 200, a: 255 };
+        let mut recorder = ShotRecorder::new(vec![Rect { origin: Coordinate(0, 300), size: Size(100, 100) }], Layout::default());
+
+        for frame in 0..4 {
+            let mut pixels = vec![background; 640*360];
+            // the projectile, moving 10 pixels right each frame
+            for y in 200..203 {
+                pixels[y*640 + 100 + 10*frame..y*640 + 103 + 10*frame].fill(ARGB { r: 255, g: 255, b: 255, a: 255 });
+            }
+            // ignored movement
+            pixels[350*640 + frame] = ARGB { r: 0, g: 0, b: 0, a: 255 };
+
+            recorder.record(&Bitmap::new(&mut pixels, 640), frame as f32 * 0.1);
+        }
+
+        let observations = recorder.observations();
+        assert_eq!(observations.len(), 3);
+        assert_eq!(observations[0], Observation { time: 0.05, position: (106.0, 201.0) });
+        assert_eq!(observations[2].position, (126.0, 201.0));
+    }
+
+    #[test]
+    fn profile_test() {
+        let physics = Physics { wind_constant: 0.0041, path_constant: 2.95 };
+
+        assert_eq!(parse_profile(&format_profile(&physics)).unwrap(), physics);
+        assert_eq!(parse_profile("[physics]\nwind_constant = 0\npath_constant = 3").unwrap(), Physics { wind_constant: 0.0, path_constant: 3.0 });
+
+        let invalid_key = |profile: &str| match parse_profile(profile) {
+            Err(CalibrationError::InvalidProfile(SettingsError::Invalid { key, .. })) => key,
+            other => panic!("{profile:?} gave {other:?}")
+        };
+        assert_eq!(invalid_key("[physics]\nwind_constant = 1"), "physics.path_constant");
+        assert_eq!(invalid_key("[physics]\nwind_constant = -1\npath_constant = 3"), "physics.wind_constant");
+        assert_eq!(invalid_key("[physics]\nwind_constant = 1\npath_constant = 0"), "physics.path_constant");
+        assert_eq!(invalid_key("[physics]\nwind_constant = 1\npath_constant = nan"), "physics.path_constant");
+        assert_eq!(invalid_key("[physics]\nwind_constant = 1\npath_constant = inf"), "physics.path_constant");
+
+        assert!(matches!(parse_profile("[physics]\nwind_constant = 1\ngravity = 2"), Err(CalibrationError::InvalidProfile(SettingsError::Parse(_)))));
+        assert!(matches!(parse_profile("wind_constant = 1\npath_constant = 3"), Err(CalibrationError::InvalidProfile(SettingsError::Parse(_)))));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::Size;
+use crate::bitmap::ARGB;
+
+use super::{check_dimensions, CodecError};
+
+const BMP_FILE_HEADER_SIZE: usize = 14;
+const BMP_INFO_HEADER_SIZE: usize = 40;
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, CodecError> {
+    bytes.get(offset..offset+2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or(CodecError::InvalidData("unexpected end of file"))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, CodecError> {
+    bytes.get(offset..offset+4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(CodecError::InvalidData("unexpected end of file"))
+}
+
+/// The position and width of a colour channel within a 32 bit `BI_BITFIELDS` pixel.
+#[derive(Clone, Copy)]
+struct ChannelMask {
+    shift: u32,
+    bits: u32
+}
+
+impl ChannelMask {
+    /// `None` for an empty mask. Masks must be a single run of set bits.
+    fn new(mask: u32) -> Result<Option<Self>, CodecError> {
+        if mask == 0 {
+            return Ok(None)
+        }
+
+        let shift = mask.trailing_zeros();
+        let bits = (mask >> shift).trailing_ones();
+        if mask >> shift >> bits != 0 {
+            return Err(CodecError::InvalidData("BMP channel mask is not contiguous"))
+        }
+
+        Ok(Some(Self { shift, bits }))
+    }
+
+    /// Extract the channel from a pixel, scaled to 8 bits.
+    fn extract(self, pixel: u32) -> u8 {
+        let max = (1u64 << self.bits) - 1;
+        let value = (pixel as u64 >> self.shift) & max;
+        (value * 255 / max) as u8
+    }
+}
+
+/// Decode a 24 or 32 bit uncompressed BMP file.
+///
+/// 32 bit `BI_RGB` files are treated as opaque, as most leave the fourth byte as zero.
+/// `BI_BITFIELDS` files are only given an alpha channel if their header has an alpha mask.
+///
+/// Pixels are returned bottom-up, the same row order used by `Bitmap`.
+pub fn decode_bmp(bytes: &[u8]) -> Result<(Vec<ARGB>, Size<u32>), CodecError> {
+    if bytes.get(0..2) != Some(b"BM") {
+        return Err(CodecError::InvalidData("missing BMP signature"))
+    }
+
+    let pixel_offset = read_u32(bytes, 10)? as usize;
+    let header = BMP_FILE_HEADER_SIZE;
+
+    let header_size = read_u32(bytes, header)? as usize;
+    let width = read_u32(bytes, header + 4)? as i32;
+    let height = read_u32(bytes, header + 8)? as i32;
+    let bit_count = read_u16(bytes, header + 14)?;
+    let compression = read_u32(bytes, header + 16)?;
+
+    if width <= 0 || height == 0 {
+        return Err(CodecError::InvalidData("invalid BMP dimensions"))
+    }
+    if !(compression == BI_RGB || (compression == BI_BITFIELDS && bit_count == 32)) {
+        return Err(CodecError::Unsupported(format!("BMP compression type {compression}")))
+    }
+    if bit_count != 24 && bit_count != 32 {
+        return Err(CodecError::Unsupported(format!("{bit_count} bit BMP")))
+    }
+
+    // the red, green and blue masks follow a BITMAPINFOHEADER, or are part of the larger headers,
+    // which also have an alpha mask
+    let masks = if compression == BI_BITFIELDS {
+        let mask = |index: usize| ChannelMask::new(read_u32(bytes, header + BMP_INFO_HEADER_SIZE + 4*index)?);
+        let alpha = if header_size > BMP_INFO_HEADER_SIZE + 12 { mask(3)? } else { None };
+        let colour_mask = |index| mask(index)?.ok_or(CodecError::InvalidData("BMP colour mask is empty"));
+
+        Some(([colour_mask(0)?, colour_mask(1)?, colour_mask(2)?], alpha))
+    } else {
+        None
+    };
+
+    let (width, top_down, height) = (width as usize, height < 0, height.unsigned_abs() as usize);
+    let bytes_per_pixel = bit_count as usize / 8;
+    // rows are padded to a multiple of 4 bytes
+    let stride = (width * bytes_per_pixel + 3) & !3;
+
+    let data = stride.checked_mul(height)
+        .and_then(|length| bytes.get(pixel_offset..pixel_offset.checked_add(length)?))
+        .ok_or(CodecError::InvalidData("pixel data is truncated"))?;
+
+    let mut pixels = Vec::with_capacity(width*height);
+    for row in 0..height {
+        let file_row = if top_down { height - 1 - row } else { row };
+        let row_data = &data[file_row*stride..file_row*stride + width*bytes_per_pixel];
+
+        pixels.extend(row_data.chunks_exact(bytes_per_pixel).map(|p| match masks {
+            Some(([r, g, b], a)) => {
+                let pixel = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+                ARGB { r: r.extract(pixel), g: g.extract(pixel), b: b.extract(pixel), a: a.map_or(255, |a| a.extract(pixel)) }
+            },
+            None => ARGB { b: p[0], g: p[1], r: p[2], a: 255 }
+        }));
+    }
+
+    Ok((pixels, Size(width as u32, height as u32)))
+}
+
+/// Encode pixels as a 32 bit bottom-up BMP, the same layout as `create_bitmap_header` in `window_winapi`.
+///
+/// The alpha channel is written to the fourth byte of each pixel, but isn't read back by `decode_bmp`.
+pub fn encode_bmp(pixels: &[ARGB], dimensions: Size<u32>) -> Result<Vec<u8>, CodecError> {
+    check_dimensions(pixels, dimensions)?;
+    let image_size = dimensions.0*dimensions.1*4;
+    let pixel_offset = (BMP_FILE_HEADER_SIZE + BMP_INFO_HEADER_SIZE) as u32;
+
+    let mut bytes = Vec::with_capacity((pixel_offset + image_size) as usize);
+
+    // BITMAPFILEHEADER
+    bytes.extend(b"BM");
+    bytes.extend((pixel_offset + image_size).to_le_bytes());
+    bytes.extend([0; 4]);
+    bytes.extend(pixel_offset.to_le_bytes());
+
+    // BITMAPINFOHEADER
+    bytes.extend((BMP_INFO_HEADER_SIZE as u32).to_le_bytes());
+    bytes.extend((dimensions.0 as i32).to_le_bytes());
+    bytes.extend((dimensions.1 as i32).to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(32u16.to_le_bytes());
+    bytes.extend(BI_RGB.to_le_bytes());
+    bytes.extend(image_size.to_le_bytes());
+    // pixels per meter, colours used and important colours
+    bytes.extend([0; 16]);
+
+    for pixel in pixels {
+        bytes.extend([pixel.b, pixel.g, pixel.r, pixel.a]);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a 24 bit BMP with the given rows, listed from the top of the image down.
+    fn bmp_24(width: u32, rows_top_down: &[&[(u8, u8, u8)]], top_down: bool) -> Vec<u8> {
+        let height = rows_top_down.len() as u32;
+        let stride = (width * 3 + 3) & !3;
+        let mut bytes = Vec::new();
+
+        bytes.extend(b"BM");
+        bytes.extend((54 + stride*height).to_le_bytes());
+        bytes.extend([0; 4]);
+        bytes.extend(54u32.to_le_bytes());
+        bytes.extend(40u32.to_le_bytes());
+        bytes.extend(width.to_le_bytes());
+        let signed_height = if top_down { -(height as i32) } else { height as i32 };
+        bytes.extend(signed_height.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(24u16.to_le_bytes());
+        bytes.extend([0; 24]);
+
+        let mut write_row = |row: &[(u8, u8, u8)]| {
+            for (r, g, b) in row {
+                bytes.extend([*b, *g, *r]);
+            }
+            bytes.extend(vec![0; (stride - width*3) as usize]);
+        };
+        if top_down {
+            rows_top_down.iter().for_each(|row| write_row(row));
+        } else {
+            rows_top_down.iter().rev().for_each(|row| write_row(row));
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn decode_bmp_row_order_test() {
+        let top = [(255, 0, 0), (0, 255, 0), (0, 0, 255)];
+        let bottom = [(10, 20, 30), (40, 50, 60), (70, 80, 90)];
+
+        for top_down in [false, true] {
+            let (pixels, size) = decode_bmp(&bmp_24(3, &[&top, &bottom], top_down)).unwrap();
+
+            assert_eq!((size.0, size.1), (3, 2));
+            // first row is the bottom of the image
+            assert_eq!(pixels[0], ARGB { r: 10, g: 20, b: 30, a: 255 });
+            assert_eq!(pixels[5], ARGB { r: 0, g: 0, b: 255, a: 255 });
+        }
+    }
+
+    #[test]
+    fn bmp_round_trip_test() {
+        let pixels: Vec<ARGB> = (0..12u32).map(|i| ARGB { r: i as u8, g: 2*i as u8, b: 3*i as u8, a: 255 }).collect();
+
+        let (decoded, size) = decode_bmp(&encode_bmp(&pixels, Size(4, 3)).unwrap()).unwrap();
+
+        assert_eq!((size.0, size.1), (4, 3));
+        assert_eq!(decoded, pixels);
+    }
+
+    #[test]
+    fn decode_bmp_32_bit_test() {
+        let pixels = [ARGB { r: 10, g: 20, b: 30, a: 0 }, ARGB { r: 40, g: 50, b: 60, a: 128 }];
+        let mut bmp = encode_bmp(&pixels, Size(2, 1)).unwrap();
+
+        // BI_RGB files have no alpha channel
+        let (decoded, _) = decode_bmp(&bmp).unwrap();
+        assert_eq!(decoded, [ARGB { a: 255, ..pixels[0] }, ARGB { a: 255, ..pixels[1] }]);
+
+        // BI_BITFIELDS with a BITMAPV3INFOHEADER, the bytes of each pixel read as red, green, blue and alpha
+        bmp[10] = 70;
+        bmp[14] = 56;
+        bmp[30] = BI_BITFIELDS as u8;
+        let masks = [0x000000ffu32, 0x0000ff00, 0x00ff0000, 0xff000000];
+        bmp.splice(54..54, masks.iter().flat_map(|mask| mask.to_le_bytes()));
+
+        let (decoded, _) = decode_bmp(&bmp).unwrap();
+        assert_eq!(decoded, [ARGB { r: 30, g: 20, b: 10, a: 0 }, ARGB { r: 60, g: 50, b: 40, a: 128 }]);
+
+        // without an alpha mask the image is opaque
+        bmp[14] = 40;
+        let (decoded, _) = decode_bmp(&bmp).unwrap();
+        assert_eq!(decoded[1], ARGB { r: 60, g: 50, b: 40, a: 255 });
+    }
+
+    #[test]
+    fn decode_bmp_invalid_test() {
+        assert!(matches!(decode_bmp(b"PNG"), Err(CodecError::InvalidData(_))));
+
+        let mut truncated = bmp_24(3, &[&[(0, 0, 0); 3]], false);
+        truncated.truncate(56);
+        assert!(matches!(decode_bmp(&truncated), Err(CodecError::InvalidData(_))));
+
+        assert!(matches!(encode_bmp(&[], Size(0, 0)), Err(CodecError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,115 @@
+//! Reading and writing images to and from `ARGB` pixel buffers.
+//!
+//! All decoders return pixels bottom-up, the same row order used by `Bitmap`,
+//! and all encoders expect pixels in that order.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::Size;
+use crate::bitmap::ARGB;
+
+mod bmp;
+mod png;
+mod ppm;
+mod zlib;
+
+pub use bmp::{decode_bmp, encode_bmp};
+pub use png::{decode_png, encode_png};
+pub use ppm::{decode_ppm, encode_ppm};
+
+#[derive(Error, Debug)]
+pub enum CodecError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid image data: {0}")]
+    InvalidData(&'static str),
+    #[error("Unsupported image format: {0}")]
+    Unsupported(String)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+    Ppm
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Result<Self, CodecError> {
+        let extension = path.extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "bmp" => Ok(Self::Bmp),
+            "png" => Ok(Self::Png),
+            "ppm" => Ok(Self::Ppm),
+            other => Err(CodecError::Unsupported(format!("file extension `{other}`")))
+        }
+    }
+
+    /// Detect the format from the signature at the start of the file.
+    pub fn from_signature(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"BM") {
+            Some(Self::Bmp)
+        } else if bytes.starts_with(&png::SIGNATURE) {
+            Some(Self::Png)
+        } else if bytes.starts_with(b"P6") {
+            Some(Self::Ppm)
+        } else {
+            None
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> Result<(Vec<ARGB>, Size<u32>), CodecError> {
+        match self {
+            Self::Bmp => decode_bmp(bytes),
+            Self::Png => decode_png(bytes),
+            Self::Ppm => decode_ppm(bytes)
+        }
+    }
+
+    pub fn encode(self, pixels: &[ARGB], dimensions: Size<u32>) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Self::Bmp => encode_bmp(pixels, dimensions),
+            Self::Png => encode_png(pixels, dimensions),
+            Self::Ppm => encode_ppm(pixels, dimensions)
+        }
+    }
+}
+
+/// Check there is at least one pixel, and `pixels` has exactly `dimensions.0*dimensions.1` of them.
+fn check_dimensions(pixels: &[ARGB], dimensions: Size<u32>) -> Result<(), CodecError> {
+    if dimensions.0 == 0 || dimensions.1 == 0 {
+        return Err(CodecError::InvalidData("image has no pixels"))
+    }
+    if (dimensions.0 as usize).checked_mul(dimensions.1 as usize) != Some(pixels.len()) {
+        return Err(CodecError::InvalidData("pixel count doesn't match the image dimensions"))
+    }
+
+    Ok(())
+}
+
+/// Load an image, with the format detected from the file's contents.
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<(Vec<ARGB>, Size<u32>), CodecError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+
+    match ImageFormat::from_signature(&bytes) {
+        Some(format) => format.decode(&bytes),
+        None => ImageFormat::from_path(path)?.decode(&bytes)
+    }
+}
+
+/// Save an image, with the format chosen from the file extension.
+pub fn save_image<P: AsRef<Path>>(path: P, pixels: &[ARGB], dimensions: Size<u32>) -> Result<(), CodecError> {
+    let path = path.as_ref();
+    let bytes = ImageFormat::from_path(path)?.encode(pixels, dimensions)?;
+
+    Ok(fs::write(path, bytes)?)
+}
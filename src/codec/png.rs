@@ -0,0 +1,312 @@
+use crate::Size;
+use crate::bitmap::ARGB;
+
+use super::{check_dimensions, CodecError};
+use super::zlib::{zlib_compress, zlib_decompress};
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+const COLOUR_GREYSCALE: u8 = 0;
+const COLOUR_RGB: u8 = 2;
+const COLOUR_PALETTE: u8 = 3;
+const COLOUR_GREYSCALE_ALPHA: u8 = 4;
+const COLOUR_RGBA: u8 = 6;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverse the filter of a scanline in place, given the already unfiltered previous scanline.
+fn unfilter(filter: u8, line: &mut [u8], previous: &[u8], bpp: usize) -> Result<(), CodecError> {
+    for i in 0..line.len() {
+        let left = if i >= bpp { line[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+
+        line[i] = line[i].wrapping_add(match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(CodecError::InvalidData("invalid PNG filter type"))
+        });
+    }
+
+    Ok(())
+}
+
+/// Apply a filter to a scanline, given the previous scanline.
+fn filter(filter: u8, line: &[u8], previous: &[u8], bpp: usize, output: &mut Vec<u8>) {
+    output.push(filter);
+
+    for i in 0..line.len() {
+        let left = if i >= bpp { line[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+
+        output.push(line[i].wrapping_sub(match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            _ => paeth(left, up, up_left)
+        }));
+    }
+}
+
+struct Chunk<'a> {
+    kind: [u8; 4],
+    data: &'a [u8]
+}
+
+fn read_chunks(bytes: &[u8]) -> Result<Vec<Chunk<'_>>, CodecError> {
+    let mut chunks = Vec::new();
+    let mut pos = SIGNATURE.len();
+
+    while pos < bytes.len() {
+        let header = bytes.get(pos..pos + 8).ok_or(CodecError::InvalidData("PNG chunk is truncated"))?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+
+        let kind_and_data = bytes.get(pos + 4..pos + 8 + length).ok_or(CodecError::InvalidData("PNG chunk is truncated"))?;
+        let crc = bytes.get(pos + 8 + length..pos + 12 + length).ok_or(CodecError::InvalidData("PNG chunk is truncated"))?;
+
+        if crc32(kind_and_data) != u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+            return Err(CodecError::InvalidData("PNG chunk checksum mismatch"))
+        }
+
+        chunks.push(Chunk { kind, data: &kind_and_data[4..] });
+        pos += 12 + length;
+
+        if &kind == b"IEND" {
+            break
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Decode a non-interlaced 8 or 16 bit PNG file.
+pub fn decode_png(bytes: &[u8]) -> Result<(Vec<ARGB>, Size<u32>), CodecError> {
+    if !bytes.starts_with(&SIGNATURE) {
+        return Err(CodecError::InvalidData("missing PNG signature"))
+    }
+
+    let chunks = read_chunks(bytes)?;
+
+    let header = chunks.first()
+        .filter(|chunk| &chunk.kind == b"IHDR" && chunk.data.len() == 13)
+        .ok_or(CodecError::InvalidData("missing PNG header"))?
+        .data;
+
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let (bit_depth, colour_type, interlace) = (header[8], header[9], header[12]);
+
+    if width == 0 || height == 0 {
+        return Err(CodecError::InvalidData("invalid PNG dimensions"))
+    }
+    if interlace != 0 {
+        return Err(CodecError::Unsupported("interlaced PNG".to_string()))
+    }
+
+    let channels = match (colour_type, bit_depth) {
+        (COLOUR_PALETTE, 8) => 1,
+        (COLOUR_PALETTE, _) => return Err(CodecError::Unsupported(format!("{bit_depth} bit palette PNG"))),
+        (_, 8 | 16) => match colour_type {
+            COLOUR_GREYSCALE => 1,
+            COLOUR_RGB => 3,
+            COLOUR_GREYSCALE_ALPHA => 2,
+            COLOUR_RGBA => 4,
+            _ => return Err(CodecError::InvalidData("invalid PNG colour type"))
+        },
+        _ => return Err(CodecError::Unsupported(format!("{bit_depth} bit PNG")))
+    };
+
+    let palette: Vec<ARGB> = chunks.iter()
+        .find(|chunk| &chunk.kind == b"PLTE")
+        .map(|chunk| chunk.data.chunks_exact(3).map(|rgb| ARGB { r: rgb[0], g: rgb[1], b: rgb[2], a: 255 }).collect())
+        .unwrap_or_default();
+
+    let compressed: Vec<u8> = chunks.iter()
+        .filter(|chunk| &chunk.kind == b"IDAT")
+        .flat_map(|chunk| chunk.data.iter().copied())
+        .collect();
+    let mut data = zlib_decompress(&compressed)?;
+
+    let bytes_per_sample = bit_depth as usize / 8;
+    let bpp = channels * bytes_per_sample;
+    let stride = width.checked_mul(bpp).ok_or(CodecError::InvalidData("invalid PNG dimensions"))?;
+
+    if (stride + 1).checked_mul(height).is_none_or(|length| data.len() < length) {
+        return Err(CodecError::InvalidData("PNG image data is truncated"))
+    }
+
+    let mut previous = vec![0; stride];
+    for row in 0..height {
+        let start = row * (stride + 1);
+        let (filter_type, line) = (data[start], &mut data[start + 1..start + 1 + stride]);
+        unfilter(filter_type, line, &previous, bpp)?;
+        previous.copy_from_slice(line);
+    }
+
+    // only the most significant byte of 16 bit samples is kept
+    let sample = |line: &[u8], col: usize, channel: usize| line[(col*channels + channel) * bytes_per_sample];
+
+    // PNG rows are stored top-down
+    let mut pixels = Vec::with_capacity(width*height);
+    for row in (0..height).rev() {
+        let line = &data[row * (stride + 1) + 1..(row + 1) * (stride + 1)];
+
+        for col in 0..width {
+            pixels.push(match colour_type {
+                COLOUR_GREYSCALE => {
+                    let value = sample(line, col, 0);
+                    ARGB { r: value, g: value, b: value, a: 255 }
+                },
+                COLOUR_GREYSCALE_ALPHA => {
+                    let value = sample(line, col, 0);
+                    ARGB { r: value, g: value, b: value, a: sample(line, col, 1) }
+                },
+                COLOUR_RGB => ARGB { r: sample(line, col, 0), g: sample(line, col, 1), b: sample(line, col, 2), a: 255 },
+                COLOUR_RGBA => ARGB { r: sample(line, col, 0), g: sample(line, col, 1), b: sample(line, col, 2), a: sample(line, col, 3) },
+                _ => *palette.get(line[col] as usize).ok_or(CodecError::InvalidData("PNG palette index out of range"))?
+            });
+        }
+    }
+
+    Ok((pixels, Size(width as u32, height as u32)))
+}
+
+fn write_chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend((data.len() as u32).to_be_bytes());
+    let start = output.len();
+    output.extend(kind);
+    output.extend(data);
+    let crc = crc32(&output[start..]);
+    output.extend(crc.to_be_bytes());
+}
+
+/// Encode pixels as an 8 bit RGBA PNG.
+pub fn encode_png(pixels: &[ARGB], dimensions: Size<u32>) -> Result<Vec<u8>, CodecError> {
+    check_dimensions(pixels, dimensions)?;
+
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let stride = width * 4;
+
+    // each scanline uses the filter with the lowest sum of absolute differences
+    let mut filtered = Vec::with_capacity((stride + 1) * height);
+    let mut previous = vec![0; stride];
+    let mut line = Vec::with_capacity(stride);
+    let mut candidate = Vec::with_capacity(stride + 1);
+
+    for row in pixels.chunks_exact(width).rev() {
+        line.clear();
+        line.extend(row.iter().flat_map(|pixel| [pixel.r, pixel.g, pixel.b, pixel.a]));
+
+        let best_filter = (0..5).min_by_key(|filter_type| {
+            candidate.clear();
+            filter(*filter_type, &line, &previous, 4, &mut candidate);
+            candidate[1..].iter().map(|byte| (*byte as i8).unsigned_abs() as u64).sum::<u64>()
+        }).unwrap();
+
+        filter(best_filter, &line, &previous, 4, &mut filtered);
+        previous.clone_from(&line);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // bit depth, colour type, compression, filter and interlace methods
+    header.extend([8, COLOUR_RGBA, 0, 0, 0]);
+
+    let mut output = SIGNATURE.to_vec();
+    write_chunk(&mut output, b"IHDR", &header);
+    write_chunk(&mut output, b"IDAT", &zlib_compress(&filtered));
+    write_chunk(&mut output, b"IEND", &[]);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip_test() {
+        let pixels: Vec<ARGB> = (0..35u32).map(|i| ARGB { r: (i * 7) as u8, g: (i % 3) as u8 * 100, b: 255 - i as u8, a: (i * 5) as u8 }).collect();
+
+        let (decoded, size) = decode_png(&encode_png(&pixels, Size(5, 7)).unwrap()).unwrap();
+
+        assert_eq!((size.0, size.1), (5, 7));
+        assert_eq!(decoded, pixels);
+    }
+
+    #[test]
+    fn encode_png_invalid_test() {
+        assert!(matches!(encode_png(&[], Size(0, 3)), Err(CodecError::InvalidData(_))));
+        assert!(matches!(encode_png(&[ARGB { r: 0, g: 0, b: 0, a: 0 }; 5], Size(2, 3)), Err(CodecError::InvalidData(_))));
+    }
+
+    #[test]
+    fn decode_png_filters_test() {
+        // 4x3 RGB image written with Python's zlib, using the sub, up and paeth filters
+        let png = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+            0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x03, 0x08, 0x02, 0x00, 0x00, 0x00, 0x3b, 0x96, 0x39,
+            0x91, 0x00, 0x00, 0x00, 0x2c, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xfc, 0xcf, 0xc0, 0xc0,
+            0x08, 0xc6, 0xff, 0xff, 0x33, 0x30, 0x71, 0x8b, 0xc8, 0x69, 0x18, 0xdb, 0xb8, 0x05, 0x44, 0xa7,
+            0xe6, 0x57, 0xb2, 0x7c, 0x7b, 0xf3, 0x28, 0x22, 0x27, 0xc9, 0xe3, 0xc9, 0x26, 0xcb, 0x29, 0x2b,
+            0x01, 0x08, 0xb6, 0x0f, 0x55, 0x2d, 0xc0, 0x6c, 0x82, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e,
+            0x44, 0xae, 0x42, 0x60, 0x82
+        ];
+
+        let (decoded, size) = decode_png(&png).unwrap();
+        let rgb: Vec<(u8, u8, u8)> = decoded.iter().map(|pixel| (pixel.r, pixel.g, pixel.b)).collect();
+
+        assert_eq!((size.0, size.1), (4, 3));
+        // bottom row first
+        assert_eq!(rgb, [
+            (0, 0, 0), (128, 128, 128), (200, 100, 50), (1, 2, 3),
+            (10, 20, 30), (40, 50, 60), (70, 80, 90), (100, 110, 120),
+            (255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255)
+        ]);
+    }
+
+    #[test]
+    fn crc_test() {
+        assert_eq!(crc32(b"IEND"), 0xae426082);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::Size;
+use crate::bitmap::ARGB;
+
+use super::{check_dimensions, CodecError};
+
+/// Read the next whitespace separated header value, skipping comments.
+fn header_value(bytes: &[u8], pos: &mut usize) -> Result<u32, CodecError> {
+    loop {
+        match bytes.get(*pos) {
+            Some(b'#') => {
+                while bytes.get(*pos).is_some_and(|b| *b != b'\n') {
+                    *pos += 1;
+                }
+            },
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            Some(_) => break,
+            None => return Err(CodecError::InvalidData("PPM header is truncated"))
+        }
+    }
+
+    let start = *pos;
+    while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+        *pos += 1;
+    }
+
+    std::str::from_utf8(&bytes[start..*pos])
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .ok_or(CodecError::InvalidData("invalid PPM header value"))
+}
+
+/// Decode a binary (P6) PPM file.
+pub fn decode_ppm(bytes: &[u8]) -> Result<(Vec<ARGB>, Size<u32>), CodecError> {
+    if !bytes.starts_with(b"P6") {
+        return Err(CodecError::InvalidData("missing binary PPM signature"))
+    }
+
+    let mut pos = 2;
+    let width = header_value(bytes, &mut pos)? as usize;
+    let height = header_value(bytes, &mut pos)? as usize;
+    let max_value = header_value(bytes, &mut pos)?;
+
+    if width == 0 || height == 0 || !(1..=u16::MAX as u32).contains(&max_value) {
+        return Err(CodecError::InvalidData("invalid PPM header"))
+    }
+    // a single whitespace character separates the header from the pixel data
+    pos += 1;
+
+    let bytes_per_sample = if max_value > 255 { 2 } else { 1 };
+    let data = width.checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3*bytes_per_sample))
+        .and_then(|length| bytes.get(pos..pos.checked_add(length)?))
+        .ok_or(CodecError::InvalidData("pixel data is truncated"))?;
+
+    let sample = |i: usize| {
+        let value = if bytes_per_sample == 2 {
+            u16::from_be_bytes([data[2*i], data[2*i + 1]]) as u32
+        } else {
+            data[i] as u32
+        };
+        (value * 255 / max_value) as u8
+    };
+
+    // PPM rows are stored top-down
+    let mut pixels = Vec::with_capacity(width*height);
+    for row in (0..height).rev() {
+        for col in 0..width {
+            let i = (row*width + col) * 3;
+            pixels.push(ARGB { r: sample(i), g: sample(i + 1), b: sample(i + 2), a: 255 });
+        }
+    }
+
+    Ok((pixels, Size(width as u32, height as u32)))
+}
+
+/// Encode pixels as a binary (P6) PPM. The alpha channel is discarded.
+pub fn encode_ppm(pixels: &[ARGB], dimensions: Size<u32>) -> Result<Vec<u8>, CodecError> {
+    check_dimensions(pixels, dimensions)?;
+
+    let mut bytes = format!("P6\n{} {}\n255\n", dimensions.0, dimensions.1).into_bytes();
+    bytes.reserve(pixels.len()*3);
+
+    for row in pixels.chunks_exact(dimensions.0 as usize).rev() {
+        for pixel in row {
+            bytes.extend([pixel.r, pixel.g, pixel.b]);
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ppm_round_trip_test() {
+        let pixels: Vec<ARGB> = (0..12u32).map(|i| ARGB { r: i as u8, g: 20*i as u8, b: 255 - i as u8, a: 255 }).collect();
+
+        let (decoded, size) = decode_ppm(&encode_ppm(&pixels, Size(3, 4)).unwrap()).unwrap();
+
+        assert_eq!((size.0, size.1), (3, 4));
+        assert_eq!(decoded, pixels);
+        assert!(matches!(encode_ppm(&[], Size(0, 4)), Err(CodecError::InvalidData(_))));
+    }
+
+    #[test]
+    fn decode_ppm_header_test() {
+        let mut bytes = b"P6 # a comment\n2 1\n# another\n15\n".to_vec();
+        bytes.extend([15, 0, 0, 0, 15, 5]);
+
+        let (decoded, size) = decode_ppm(&bytes).unwrap();
+
+        assert_eq!((size.0, size.1), (2, 1));
+        assert_eq!(decoded, [ARGB { r: 255, g: 0, b: 0, a: 255 }, ARGB { r: 0, g: 255, b: 85, a: 255 }]);
+    }
+}
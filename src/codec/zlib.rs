@@ -0,0 +1,450 @@
+//! A minimal zlib (RFC 1950) and deflate (RFC 1951) implementation for the PNG codec.
+//!
+//! Decompression supports every block type. Compression uses the fixed Huffman codes with
+//! hash chained LZ77 matching, which is plenty for screenshots and debug images.
+
+use super::CodecError;
+
+const MAX_BITS: usize = 15;
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073,
+    4097, 6145, 8193, 12289, 16385, 24577
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+/// The order code lengths are stored in for dynamic Huffman blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const TRUNCATED: CodecError = CodecError::InvalidData("compressed data is truncated");
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+
+    // 5552 is the largest number of bytes that can be summed before b overflows
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+
+    (b << 16) | a
+}
+
+// ###############################
+// ######### Decompression #######
+// ###############################
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buffer: u32,
+    bit_count: u32
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, bit_buffer: 0, bit_count: 0 }
+    }
+
+    /// Read `count` bits, least significant bit first.
+    fn bits(&mut self, count: u32) -> Result<u32, CodecError> {
+        while self.bit_count < count {
+            let byte = *self.data.get(self.pos).ok_or(TRUNCATED)?;
+            self.pos += 1;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+
+        let value = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    /// Discard any bits remaining in the current byte.
+    fn align_to_byte(&mut self) {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols ordered by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; MAX_BITS + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, CodecError> {
+        // code, first code of the current length and index of that first code in symbols
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for length in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize])
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(CodecError::InvalidData("invalid Huffman code"))
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), CodecError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_length_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_length_lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_length_lengths);
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last()
+                    .ok_or(CodecError::InvalidData("repeated code length with no previous length"))?;
+                (previous, 3 + reader.bits(2)? as usize)
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize)
+        };
+
+        let repeated = lengths.get_mut(i..i + repeat)
+            .ok_or(CodecError::InvalidData("too many code lengths"))?;
+        repeated.fill(value);
+        i += repeat;
+    }
+
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), CodecError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                let base = *LENGTH_BASE.get(index).ok_or(CodecError::InvalidData("invalid length code"))?;
+                let length = base as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = distances.decode(reader)? as usize;
+                let base = *DISTANCE_BASE.get(index).ok_or(CodecError::InvalidData("invalid distance code"))?;
+                let distance = base as usize + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+
+                if distance > output.len() {
+                    return Err(CodecError::InvalidData("distance is too far back"))
+                }
+
+                // copied byte by byte as the match may overlap the bytes being written
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// Decompress raw deflate data.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let mut reader = BitReader::new(data);
+    let mut output = Vec::new();
+
+    loop {
+        let last_block = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let header = data.get(reader.pos..reader.pos + 4).ok_or(TRUNCATED)?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let complement = u16::from_le_bytes([header[2], header[3]]) as usize;
+
+                if length != !complement & 0xffff {
+                    return Err(CodecError::InvalidData("stored block length is corrupt"))
+                }
+
+                let start = reader.pos + 4;
+                output.extend(data.get(start..start + length).ok_or(TRUNCATED)?);
+                reader.pos = start + length;
+            },
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            },
+            _ => return Err(CodecError::InvalidData("invalid deflate block type"))
+        }
+
+        if last_block {
+            return Ok(output)
+        }
+    }
+}
+
+/// Decompress a zlib stream, verifying its checksum.
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let (cmf, flags) = match data {
+        [cmf, flags, ..] => (*cmf, *flags),
+        _ => return Err(TRUNCATED)
+    };
+
+    if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flags as u16).is_multiple_of(31) {
+        return Err(CodecError::InvalidData("invalid zlib header"))
+    }
+    if flags & 0x20 != 0 {
+        return Err(CodecError::Unsupported("zlib preset dictionary".to_string()))
+    }
+
+    let output = inflate(&data[2..])?;
+
+    let checksum = data.len().checked_sub(4)
+        .map(|start| u32::from_be_bytes([data[start], data[start + 1], data[start + 2], data[start + 3]]))
+        .ok_or(TRUNCATED)?;
+
+    if checksum != adler32(&output) {
+        return Err(CodecError::InvalidData("zlib checksum mismatch"))
+    }
+
+    Ok(output)
+}
+
+// ###############################
+// ########## Compression ########
+// ###############################
+
+struct BitWriter {
+    output: Vec<u8>,
+    bit_buffer: u32,
+    bit_count: u32
+}
+
+impl BitWriter {
+    /// Write the lowest `count` bits of `value`, least significant bit first.
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= value << self.bit_count;
+        self.bit_count += count;
+
+        while self.bit_count >= 8 {
+            self.output.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which is stored most significant bit first.
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length)
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.output.push(self.bit_buffer as u8);
+        }
+        self.output
+    }
+}
+
+fn write_literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.code(0x30 + symbol, 8),
+        144..=255 => writer.code(0x190 + symbol - 144, 9),
+        256..=279 => writer.code(symbol - 256, 7),
+        _ => writer.code(0xc0 + symbol - 280, 8)
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let index = LENGTH_BASE.iter().rposition(|base| *base as usize <= length).unwrap();
+    write_literal(writer, 257 + index as u32);
+    writer.bits((length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+
+    let index = DISTANCE_BASE.iter().rposition(|base| *base as usize <= distance).unwrap();
+    writer.code(index as u32, 5);
+    writer.bits((distance - DISTANCE_BASE[index] as usize) as u32, DISTANCE_EXTRA[index] as u32);
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compress data into a single fixed Huffman deflate block.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { output: Vec::with_capacity(data.len() / 4), bit_buffer: 0, bit_count: 0 };
+    // final block, fixed Huffman codes
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    // the most recent position of each hash, and the previous position with the same hash
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW_SIZE];
+
+    let insert = |pos: usize, head: &mut [usize], previous: &mut [usize]| {
+        if pos + MIN_MATCH <= data.len() {
+            let hash = hash(&data[pos..]);
+            previous[pos % WINDOW_SIZE] = head[hash];
+            head[hash] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best = (0, 0);
+
+        if pos + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash(&data[pos..])];
+            let mut chain = 0;
+
+            while candidate != usize::MAX && pos - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let length = data[candidate..].iter()
+                    .zip(&data[pos..pos + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                if length > best.0 {
+                    best = (length, pos - candidate);
+                    if length == max_length {
+                        break
+                    }
+                }
+
+                let next = previous[candidate % WINDOW_SIZE];
+                // positions further back than the window have been overwritten
+                if next == usize::MAX || next >= candidate {
+                    break
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best.0 >= MIN_MATCH {
+            write_match(&mut writer, best.0, best.1);
+            for i in pos..pos + best.0 {
+                insert(i, &mut head, &mut previous);
+            }
+            pos += best.0;
+        } else {
+            write_literal(&mut writer, data[pos] as u32);
+            insert(pos, &mut head, &mut previous);
+            pos += 1;
+        }
+    }
+
+    write_literal(&mut writer, 256);
+    writer.finish()
+}
+
+/// Compress data into a zlib stream.
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, no preset dictionary
+    let mut output = vec![0x78, 0x01];
+    output.extend(deflate(data));
+    output.extend(adler32(data).to_be_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zlib_round_trip_test() {
+        let mut data = b"shellshock shellshock shellshock tracer".repeat(50);
+        data.extend((0..5000u32).map(|i| (i * 7 % 251) as u8));
+
+        let compressed = zlib_compress(&data);
+
+        assert!(compressed.len() < data.len());
+        assert_eq!(zlib_decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn inflate_stored_and_dynamic_test() {
+        // "hello" in a stored block
+        let stored = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+        assert_eq!(inflate(&stored).unwrap(), b"hello");
+
+        // compressed by zlib with dynamic Huffman codes
+        let expected = b"aabad dcaadacca baabaaaacacaa dabaa bacaababcabbdcadaccabba aacbdadabcaaaaaacbb bacaacadbacdbcbab aaaaabaaaa ababaabbaaa";
+        let dynamic = [
+            0x78, 0x01, 0x05, 0xc1, 0x01, 0x01, 0x00, 0x30, 0x0c, 0xc3, 0x20, 0x2b, 0xb7, 0x46, 0x3a, 0xff,
+            0x1a, 0x0e, 0xe4, 0xde, 0x8d, 0xb3, 0x79, 0x11, 0xcc, 0x78, 0x27, 0x5e, 0x46, 0x9a, 0xba, 0x39,
+            0x9b, 0xf2, 0x58, 0xe7, 0x34, 0x60, 0xf5, 0x32, 0xe6, 0xb2, 0x6b, 0xe9, 0x81, 0xe0, 0x49, 0x14,
+            0x3e, 0x4f, 0x81, 0x2b, 0xca
+        ];
+        assert_eq!(zlib_decompress(&dynamic).unwrap(), expected);
+    }
+}
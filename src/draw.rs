@@ -0,0 +1,354 @@
+use crate::{Coordinate, Rect, Size};
+use crate::bitmap::{Bitmap, ARGB};
+use crate::font::{draw_text, text_size, TextStyle};
+use crate::impact::ImpactMarker;
+use crate::tracking::TrackingStatus;
+use crate::trajectory::Trajectory;
+
+/// The length of the dashes, and the gaps between them, of trajectories drawn, unless set in the settings file.
+pub const DOT_LENGTH: f32 = 4.0;
+
+/// The colour and width the current trajectory is drawn with, on the live overlay and on screenshots, unless set in the settings file.
+pub const TRAJECTORY_COLOUR: ARGB = ARGB { r: 200, b: 100, g: 100, a: 255 };
+pub const TRAJECTORY_WIDTH: u32 = 2;
+
+/// The colour of the crosshair where a shot lands, and of the outline of its blast.
+const IMPACT_COLOUR: ARGB = ARGB { r: 255, g: 255, b: 255, a: 230 };
+const BLAST_FILL: ARGB = ARGB { r: 255, g: 120, b: 40, a: 50 };
+/// The colour tanks inside a blast are circled with.
+const HIT_COLOUR: ARGB = ARGB { r: 255, g: 230, b: 40, a: 255 };
+/// How far each arm of the impact crosshair reaches from its centre, in pixels.
+const CROSSHAIR_REACH: f32 = 8.0;
+
+/// How a trajectory is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrajectoryStyle {
+    pub colour: ARGB,
+    pub width: u32,
+    /// The length of the dashes, and the gaps between them, in pixels
+    pub dash_length: f32
+}
+
+impl Default for TrajectoryStyle {
+    fn default() -> Self {
+        Self { colour: TRAJECTORY_COLOUR, width: TRAJECTORY_WIDTH, dash_length: DOT_LENGTH }
+    }
+}
+
+/// Draw `colour` over `pixel`, where `coverage` is the fraction of the pixel covered.
+///
+/// Pixels are stored with premultiplied alpha, as the overlay window expects.
+/// Opaque pixels, such as those of screenshots, are the same either way.
+fn blend(pixel: &mut ARGB, colour: ARGB, coverage: f32) {
+    let source = ARGB { a: (colour.a as f32 * coverage.clamp(0.0, 1.0)).round() as u8, ..colour }.as_premult_alpha();
+    let remaining = 255 - source.a as u32;
+    let over = |source: u8, destination: u8| source + ((destination as u32 * remaining + 127) / 255) as u8;
+
+    *pixel = ARGB {
+        r: over(source.r, pixel.r),
+        g: over(source.g, pixel.g),
+        b: over(source.b, pixel.b),
+        a: over(source.a, pixel.a)
+    };
+}
+
+/// Blend `colour` over a single pixel, ignoring pixels outside the bitmap.
+pub(crate) fn blend_at(bitmap: &mut Bitmap<ARGB>, x: i32, y: i32, colour: ARGB, coverage: f32) {
+    if coverage <= 0.0 || x < 0 || y < 0 || x >= bitmap.width as i32 || y >= bitmap.height() as i32 {
+        return
+    }
+
+    let index = y as usize * bitmap.width + x as usize;
+    blend(&mut bitmap.inner[index], colour, coverage);
+}
+
+/// Blend a square of `width` pixels centred on `point`, ignoring any pixels outside the bitmap.
+fn plot(bitmap: &mut Bitmap<ARGB>, point: Coordinate<i32>, colour: ARGB, width: u32) {
+    let offset = (width as i32 - 1) / 2;
+
+    for y in point.1-offset..point.1-offset+width as i32 {
+        for x in point.0-offset..point.0-offset+width as i32 {
+            blend_at(bitmap, x, y, colour, 1.0);
+        }
+    }
+}
+
+/// Draw an aliased line using Bresenham's algorithm, `width` pixels wide.
+///
+/// Coordinates are relative to bottom-left.
+pub fn draw_line(bitmap: &mut Bitmap<ARGB>, from: Coordinate<i32>, to: Coordinate<i32>, colour: ARGB, width: u32) {
+    let dx = (to.0 - from.0).abs();
+    let dy = -(to.1 - from.1).abs();
+    let step_x = if from.0 < to.0 { 1 } else { -1 };
+    let step_y = if from.1 < to.1 { 1 } else { -1 };
+
+    let mut error = dx + dy;
+    let mut current = from;
+
+    loop {
+        plot(bitmap, current, colour, width);
+
+        if current.0 == to.0 && current.1 == to.1 {
+            break
+        }
+
+        let error2 = 2*error;
+        if error2 >= dy {
+            error += dy;
+            current.0 += step_x;
+        }
+        if error2 <= dx {
+            error += dx;
+            current.1 += step_y;
+        }
+    }
+}
+
+/// The distance from `point` to the line segment between `from` and `to`.
+fn segment_distance(point: (f32, f32), from: (f32, f32), to: (f32, f32)) -> f32 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length_squared = dx*dx + dy*dy;
+    let t = if length_squared == 0.0 { 0.0 } else { (((point.0 - from.0)*dx + (point.1 - from.1)*dy) / length_squared).clamp(0.0, 1.0) };
+
+    (point.0 - from.0 - t*dx).hypot(point.1 - from.1 - t*dy)
+}
+
+/// Draw an anti-aliased line `width` pixels wide, with rounded ends.
+///
+/// Pixels are covered by how far their centre is inside the stroke, so a width of 1 gives a thin anti-aliased line.
+/// Coordinates are relative to bottom-left, with integer coordinates at the centre of pixels.
+pub fn draw_line_aa(bitmap: &mut Bitmap<ARGB>, from: (f32, f32), to: (f32, f32), colour: ARGB, width: f32) {
+    let half_width = width / 2.0;
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    // walk along the major axis, covering the pixels across the stroke at each step
+    let steep = dy.abs() > dx.abs();
+    let (major, minor) = if steep { ((from.1, to.1), (from.0, to.0)) } else { ((from.0, to.0), (from.1, to.1)) };
+    let slope = if major.1 == major.0 { 0.0 } else { (minor.1 - minor.0) / (major.1 - major.0) };
+    let reach = half_width * slope.hypot(1.0) + 1.0;
+
+    let start = (major.0.min(major.1) - half_width - 1.0).floor() as i32;
+    let end = (major.0.max(major.1) + half_width + 1.0).ceil() as i32;
+    for along in start..=end {
+        let t = (along as f32 - major.0).clamp(major.0.min(major.1) - major.0, major.0.max(major.1) - major.0);
+        let centre = minor.0 + slope * t;
+
+        for across in (centre - reach).floor() as i32..=(centre + reach).ceil() as i32 {
+            let (x, y) = if steep { (across, along) } else { (along, across) };
+            let coverage = half_width + 0.5 - segment_distance((x as f32, y as f32), from, to);
+            blend_at(bitmap, x, y, colour, coverage.min(1.0));
+        }
+    }
+}
+
+/// Fill an anti-aliased circle. Coordinates are relative to bottom-left.
+pub fn fill_circle(bitmap: &mut Bitmap<ARGB>, centre: (f32, f32), radius: f32, colour: ARGB) {
+    let reach = radius + 1.0;
+
+    for y in (centre.1 - reach).floor() as i32..=(centre.1 + reach).ceil() as i32 {
+        for x in (centre.0 - reach).floor() as i32..=(centre.0 + reach).ceil() as i32 {
+            let distance = (x as f32 - centre.0).hypot(y as f32 - centre.1);
+            blend_at(bitmap, x, y, colour, (radius + 0.5 - distance).min(1.0));
+        }
+    }
+}
+
+/// Draw the outline of an anti-aliased circle, `width` pixels wide. Coordinates are relative to bottom-left.
+pub fn draw_circle(bitmap: &mut Bitmap<ARGB>, centre: (f32, f32), radius: f32, colour: ARGB, width: f32) {
+    let reach = radius + width / 2.0 + 1.0;
+
+    for y in (centre.1 - reach).floor() as i32..=(centre.1 + reach).ceil() as i32 {
+        for x in (centre.0 - reach).floor() as i32..=(centre.0 + reach).ceil() as i32 {
+            let distance = (x as f32 - centre.0).hypot(y as f32 - centre.1);
+            blend_at(bitmap, x, y, colour, (width / 2.0 + 0.5 - (distance - radius).abs()).min(1.0));
+        }
+    }
+}
+
+/// Fill a rectangle, ignoring any of it outside the bitmap. Coordinates are relative to bottom-left.
+pub fn fill_rect(bitmap: &mut Bitmap<ARGB>, rect: Rect, colour: ARGB) {
+    let bounds = Rect { origin: Coordinate(0, 0), size: Size(bitmap.width, bitmap.height()) };
+    let Some(rect) = rect.intersection(&bounds) else {
+        return
+    };
+
+    for y in rect.origin.1..rect.end().1 {
+        for x in rect.origin.0..rect.end().0 {
+            blend(&mut bitmap.inner[y*bitmap.width + x], colour, 1.0);
+        }
+    }
+}
+
+/// Draws each path of the trajectory as an anti-aliased dotted line, see `Trajectory::dashes`.
+pub fn draw_trajectory(bitmap: &mut Bitmap<ARGB>, trajectory: &Trajectory, style: TrajectoryStyle) {
+    for (from, to) in trajectory.dashes(style.dash_length) {
+        draw_line_aa(bitmap, (from.0 as f32, from.1 as f32), (to.0 as f32, to.1 as f32), style.colour, style.width as f32);
+    }
+}
+
+/// The status indicator, a small square in the top-left corner.
+fn status_indicator(dimensions: Size<u32>) -> Rect {
+    let top = (dimensions.1 as usize).saturating_sub(10);
+    Rect { origin: Coordinate(10, top.saturating_sub(12)), size: Size(12, 12) }
+}
+
+/// The bottom-left corner of line `line` of the labels next to the status indicator, counting down from zero.
+pub fn panel_position(dimensions: Size<u32>, line: u32) -> Coordinate<i32> {
+    let indicator = status_indicator(dimensions);
+    let line_height = text_size("", TextStyle::default().scale).1 as i32 + 6;
+    Coordinate(indicator.end().0 as i32 + 6, indicator.origin.1 as i32 - 1 - line as i32 * line_height)
+}
+
+/// Draw a crosshair where a shot lands and, if it explodes, the extent of the blast with the tanks inside it circled.
+pub fn draw_impact(bitmap: &mut Bitmap<ARGB>, marker: &ImpactMarker) {
+    let centre = (marker.position.0 as f32, marker.position.1 as f32);
+
+    if let Some(radius) = marker.blast_radius {
+        fill_circle(bitmap, centre, radius, BLAST_FILL);
+        draw_circle(bitmap, centre, radius, IMPACT_COLOUR, 1.0);
+    }
+    for window in &marker.hits {
+        let tank_centre = window.centre();
+        let radius = (window.size.0 as f32).hypot(window.size.1 as f32) / 2.0 + 3.0;
+        draw_circle(bitmap, (tank_centre.0 as f32, tank_centre.1 as f32), radius, HIT_COLOUR, 2.0);
+    }
+
+    draw_line_aa(bitmap, (centre.0 - CROSSHAIR_REACH, centre.1), (centre.0 + CROSSHAIR_REACH, centre.1), IMPACT_COLOUR, 2.0);
+    draw_line_aa(bitmap, (centre.0, centre.1 - CROSSHAIR_REACH), (centre.0, centre.1 + CROSSHAIR_REACH), IMPACT_COLOUR, 2.0);
+}
+
+/// Draw an indicator of whether the tank is currently found, held at its last position or lost,
+/// as a small square in the top-left corner labelled with the status.
+pub fn draw_status(bitmap: &mut Bitmap<ARGB>, status: &TrackingStatus) {
+    let dimensions = bitmap.dimensions();
+    fill_rect(bitmap, status_indicator(dimensions), status.indicator_colour());
+    draw_text(bitmap, &status.label(), panel_position(dimensions, 0), TextStyle::default());
+}
+
+/// The bottom-left corner to draw `label` at, to label `point`.
+///
+/// The label is drawn above and to the side of the point, moving it back on screen near the edges.
+pub fn label_position(label: &str, point: Coordinate<i32>, dimensions: Size<u32>) -> Coordinate<i32> {
+    const GAP: i32 = 8;
+
+    let size = text_size(label, TextStyle::default().scale);
+    let (width, height) = (size.0 as i32, size.1 as i32);
+
+    let x = if point.0 + GAP + width < dimensions.0 as i32 { point.0 + GAP } else { point.0 - GAP - width };
+    let y = if point.1 + GAP + height < dimensions.1 as i32 { point.1 + GAP } else { point.1 - GAP - height };
+
+    Coordinate(x.max(0), y.max(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapon::ProjectilePath;
+
+    const WHITE: ARGB = ARGB { r: 255, g: 255, b: 255, a: 255 };
+
+    #[test]
+    fn draw_line_test() {
+        let mut pixels = [ARGB::from(0); 25];
+        let mut bitmap = Bitmap::new(&mut pixels, 5);
+
+        draw_line(&mut bitmap, Coordinate(0, 0), Coordinate(4, 2), WHITE, 1);
+
+        let drawn: Vec<usize> = pixels.iter().enumerate()
+            .filter(|(_, pixel)| **pixel == WHITE)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(drawn, [0, 6, 7, 13, 14]);
+    }
+
+    #[test]
+    fn draw_line_clipped_test() {
+        let mut pixels = [ARGB::from(0); 25];
+        let mut bitmap = Bitmap::new(&mut pixels, 5);
+
+        draw_line(&mut bitmap, Coordinate(-3, 2), Coordinate(8, 2), WHITE, 1);
+
+        assert!(pixels[10..15].iter().all(|pixel| *pixel == WHITE));
+        assert_eq!(pixels.iter().filter(|pixel| **pixel == WHITE).count(), 5);
+    }
+
+    #[test]
+    fn blend_test() {
+        let mut pixels = [ARGB { r: 0, g: 0, b: 200, a: 255 }, ARGB::from(0)];
+        let mut bitmap = Bitmap::new(&mut pixels, 2);
+        let translucent_red = ARGB { r: 255, g: 0, b: 0, a: 128 };
+
+        fill_rect(&mut bitmap, Rect { origin: Coordinate(0, 0), size: Size(5, 5) }, translucent_red);
+
+        // over an opaque pixel, and over a transparent one where the result is premultiplied
+        assert_eq!(pixels[0], ARGB { r: 128, g: 0, b: 100, a: 255 });
+        assert_eq!(pixels[1], translucent_red.as_premult_alpha());
+    }
+
+    #[test]
+    fn draw_line_aa_test() {
+        let mut pixels = [ARGB::from(0); 100];
+        let mut bitmap = Bitmap::new(&mut pixels, 10);
+
+        draw_line_aa(&mut bitmap, (1.0, 4.5), (8.0, 4.5), WHITE, 1.0);
+
+        // halfway between two rows, so both are half covered
+        for x in 1..=8 {
+            assert_eq!((pixels[40 + x].a, pixels[50 + x].a), (128, 128), "column {x}");
+            assert_eq!((pixels[30 + x].a, pixels[60 + x].a), (0, 0), "column {x}");
+        }
+        assert_eq!(pixels[49].a, 0);
+    }
+
+    #[test]
+    fn draw_trajectory_test() {
+        let mut pixels = [ARGB::from(0); 200];
+        let mut bitmap = Bitmap::new(&mut pixels, 20);
+
+        // the dashes carry on around the corner
+        let trajectory = Trajectory::from_paths(vec![ProjectilePath::through(&[(0, 0), (6, 0), (6, 9)], false, 2.0)]);
+        draw_trajectory(&mut bitmap, &trajectory, TrajectoryStyle { colour: WHITE, width: 1, dash_length: 3.0 });
+
+        let bottom: Vec<bool> = pixels[..7].iter().map(|pixel| *pixel == WHITE).collect();
+        assert_eq!(bottom, [true, true, true, true, false, false, true]);
+        let right: Vec<bool> = (0..10).map(|y| pixels[y*20 + 6] == WHITE).collect();
+        assert_eq!(right, [true, true, true, true, false, false, true, true, true, true]);
+    }
+
+    #[test]
+    fn fill_circle_test() {
+        let mut pixels = [ARGB::from(0); 121];
+        let mut bitmap = Bitmap::new(&mut pixels, 11);
+
+        fill_circle(&mut bitmap, (5.0, 5.0), 3.0, WHITE);
+
+        assert_eq!(pixels[5*11 + 5], WHITE);
+        assert_eq!(pixels[5*11 + 8].a, 128);
+        assert_eq!(pixels[5*11 + 9].a, 0);
+        assert_eq!(pixels[9*11 + 9].a, 0);
+    }
+
+    #[test]
+    fn label_position_test() {
+        let dimensions = Size(200, 100);
+
+        assert_eq!(label_position("P70 A45", Coordinate(20, 20), dimensions), Coordinate(28, 28));
+        // moved to the other side of the point near the edges
+        assert_eq!(label_position("P70 A45", Coordinate(190, 95), dimensions), Coordinate(190 - 8 - 82, 95 - 8 - 14));
+    }
+
+    #[test]
+    fn draw_circle_test() {
+        let mut pixels = [ARGB::from(0); 121];
+        let mut bitmap = Bitmap::new(&mut pixels, 11);
+
+        draw_circle(&mut bitmap, (5.0, 5.0), 4.0, WHITE, 1.0);
+
+        // on the circle, and either side of it
+        for (x, y) in [(9, 5), (1, 5), (5, 9), (5, 1)] {
+            assert_eq!(pixels[y*11 + x], WHITE);
+        }
+        assert_eq!(pixels[5*11 + 5].a, 0);
+        assert_eq!(pixels[5*11 + 7].a, 0);
+        assert_eq!(pixels[10*11 + 10].a, 0);
+    }
+}
@@ -1,80 +1,286 @@
 use std::io::Write;
 use std::num::ParseIntError;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::error::Error;
-use std::io;
+use std::{io, thread};
 use std::sync::mpsc::channel;
 
-use winapi::shared::windef::{HWND, HBITMAP, HPEN};
-
-use crate::tank::Direction;
+use crate::tank::{Border, Direction};
 use crate::{
-    Size,
     Coordinate,
-    bitmap::Bitmap,
-    WindowsMessageLoop,
-    window_winapi::{
-        draw_bitmap, screen_capture, bitmap_bits_to_buffer, draw_tank_curve, object_cleanup, clear_bitmap
-    },
-    image_processing::find_tank,
-    tank::Tank
+    Size,
+    bitmap::{BitmapBuf, ARGB},
+    backend::{FrameSource, OverlaySink},
+    draw::{label_position, panel_position, TrajectoryStyle},
+    fan::{fan, FanConfig},
+    font::TextStyle,
+    impact::impact_markers,
+    hud::{GlyphSet, HudReading},
+    image_processing::{estimate_direction, DirectionEstimate, FrameScores, Team, MIN_DIRECTION_CONFIDENCE},
+    settings::Settings,
+    tank::Tank,
+    terrain::Terrain,
+    tracking::{TrackingConfig, TrackingState, TrackingStatus},
+    trajectory::Trajectory,
+    viewport::{Layout, Viewport},
+    weapon::{Weapon, CATALOGUE}
 };
 
-const LOOP_DURATION: Duration = Duration::from_millis(100);
+/// How long to wait between processing frames, unless set in the settings file.
+pub const LOOP_DURATION: Duration = Duration::from_millis(100);
+
+pub struct Config<S: FrameSource, O: OverlaySink> {
+    pub frame_source: S,
+    pub overlay: O,
+    pub dimensions: Size<u32>,
+    pub tracking: TrackingConfig,
+    /// The trajectory style, frame interval and physics to use, see `Settings`
+    pub settings: Settings,
+    /// The grid of neighbouring shots to draw, if starting in fan mode
+    pub fan: Option<FanConfig>,
+    /// The templates the HUD is read with, see `Settings::glyph_sheet`. The HUD isn't read without them
+    pub glyphs: Option<GlyphSet>
+}
+
+/// Options for a processed frame, which can be changed between frames.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameOptions {
+    /// The direction to use instead of estimating it from the tank's sprite
+    pub direction_override: Option<Direction>,
+    /// Typed values to use instead of those read from the HUD
+    pub power_override: Option<u8>,
+    pub angle_override: Option<i8>,
+    pub wind_override: Option<i8>,
+    /// How the current trajectory is drawn
+    pub style: TrajectoryStyle,
+    /// Draw the trajectories of neighbouring power and angle values around the current one
+    pub fan: Option<FanConfig>,
+    /// The templates the HUD is read with, derived from the game's own characters. The HUD isn't read without them
+    pub glyphs: Option<GlyphSet>,
+    /// The proportions tanks, the menu bar and the HUD are found with, see `Settings::layout`
+    pub layout: Layout
+}
+
+/// A line typed into the input channel.
+enum Input {
+    /// New values for the shot, with the direction if it was given
+    Shot(Tank, Option<Direction>),
+    Weapon(Weapon),
+    Border(Border),
+    /// Turn fan mode on with this grid, or off
+    Fan(Option<FanConfig>),
+    /// Go back to reading the power, angle and wind from the HUD
+    Hud
+}
+
+/// What was found in a processed frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameReport {
+    pub status: TrackingStatus,
+    /// `None` if the HUD wasn't read, as there are no glyphs to read it with
+    pub hud: Option<HudReading>,
+    /// `None` if the direction was overridden, or couldn't be estimated this frame
+    pub direction: Option<DirectionEstimate>
+}
+
+/// The values the trajectory is drawn for, shown below the status.
+pub fn tank_summary(tank: &Tank) -> String {
+    let weapon = CATALOGUE.iter().find(|(_, weapon)| *weapon == tank.weapon).map_or("custom", |(name, _)| *name);
+    format!("Power {}  Angle {}  Wind {:+}  Facing {:?}  {weapon}", tank.power, tank.angle, tank.wind, tank.direction)
+}
 
-/// The object handles must be exclusive pointers as they are deleted after use.
-pub struct WindowsObjects {
-    pub bitmap: HBITMAP,
-    pub pen: HPEN
+/// The power and angle of `tank`, to label where its trajectory lands, and the bottom-left corner to draw it at.
+/// `None` if the trajectory doesn't land on screen.
+pub fn impact_label(tank: &Tank, trajectory: &Trajectory, dimensions: Size<u32>) -> Option<(String, Coordinate<i32>)> {
+    let impact = trajectory.impact()?;
+    let label = format!("P{} A{}", tank.power, tank.angle);
+    let position = label_position(&label, impact, dimensions);
+    Some((label, position))
 }
 
-impl Drop for WindowsObjects {
-    fn drop(&mut self) {
-        unsafe { object_cleanup(self.bitmap, self.pen) }
+/// Capture a single frame, read the HUD, locate the tank and draw the trajectory of its weapon onto the overlay,
+/// marking where it lands and the tanks in the blast, and labelled with the tank's parameters.
+///
+/// A frame the tank isn't found in isn't an error, unless it has been missing for longer than the tracking timeout.
+/// The HUD is only read with `options.glyphs`, as where its readouts are hasn't been checked against captures of the game.
+/// HUD values that can't be read keep their previous value, and are reported in the returned `FrameReport`.
+/// Values overridden in `options` are used instead of those read from the HUD.
+/// The tank's direction is estimated from its sprite, unless overridden in `options`.
+pub fn process_frame<S: FrameSource, O: OverlaySink>(
+    frame_source: &mut S,
+    overlay: &mut O,
+    screen_buffer: &mut BitmapBuf<ARGB>,
+    tracking: &mut TrackingState,
+    tank: &mut Tank,
+    options: &FrameOptions
+) -> Result<FrameReport, Box<dyn Error>> {
+    // the game window may have been resized
+    let dimensions = frame_source.dimensions();
+    if screen_buffer.dimensions() != dimensions {
+        screen_buffer.resize(dimensions, 0.into());
+    }
+
+    let mut screen = screen_buffer.as_bitmap();
+    frame_source.capture(&mut screen)?;
+    let captured_at = Instant::now();
+    let layout = options.layout;
+    let viewport = Viewport::from_dimensions(dimensions, layout);
+
+    // scored once here and shared by every search of the frame
+    let scores = FrameScores::new(&screen);
+    let status = tracking.track(&scores, layout, captured_at)?;
+
+    // the direction is needed before the wind, which is relative to it
+    let direction = match (options.direction_override, status) {
+        (Some(direction), _) => {
+            tank.direction = direction;
+            None
+        },
+        (None, TrackingStatus::Found { position }) => estimate_direction(&screen, position, layout),
+        _ => None
+    };
+    if let Some(estimate) = direction.filter(|estimate| estimate.confidence >= MIN_DIRECTION_CONFIDENCE) {
+        tank.direction = estimate.direction;
+    }
+
+    let hud = options.glyphs.as_ref().map(|glyphs| HudReading::read(&screen, glyphs, &viewport));
+    if let Some(hud) = hud {
+        hud.apply(tank);
+    }
+    tank.power = options.power_override.unwrap_or(tank.power);
+    tank.angle = options.angle_override.unwrap_or(tank.angle);
+    tank.wind = options.wind_override.unwrap_or(tank.wind);
+
+    if let Some(position) = status.position() {
+        tank.screen_position = position;
+        let terrain = Terrain::extract(&screen, layout);
+
+        // only look for the other tanks when they change the path or could be hit, see `TrackingState::tanks`
+        let mut tanks = tank.weapon.needs_targets().then(|| tracking.tanks(&scores, layout, &status, captured_at).to_vec());
+        let targets: Vec<_> = tanks.iter().flatten()
+            .filter(|candidate| candidate.team == Team::Enemy)
+            .map(|candidate| candidate.position)
+            .collect();
+
+        let trajectory = match &options.fan {
+            Some(config) => {
+                let members = fan(tank, &viewport, Some(&terrain), &targets, config, options.style);
+                for member in &members {
+                    overlay.draw_trajectory(&member.trajectory, member.style)?;
+                }
+                members.into_iter().find(|member| member.selected).map(|member| member.trajectory).unwrap_or_default()
+            },
+            None => {
+                let trajectory = tank.trajectory(&viewport, Some(&terrain), &targets);
+                overlay.draw_trajectory(&trajectory, options.style)?;
+                trajectory
+            }
+        };
+
+        if trajectory.impact().is_some() && tanks.is_none() {
+            tanks = Some(tracking.tanks(&scores, layout, &status, captured_at).to_vec());
+        }
+        for marker in impact_markers(tank, &trajectory, &viewport, tanks.as_deref().unwrap_or_default()) {
+            overlay.draw_impact(&marker)?;
+        }
+
+        if let Some((label, position)) = impact_label(tank, &trajectory, dimensions) {
+            overlay.draw_text(&label, position, TextStyle::default())?;
+        }
     }
+    overlay.draw_status(&status)?;
+    overlay.draw_text(&tank_summary(tank), panel_position(dimensions, 1), TextStyle::default())?;
+
+    overlay.present()?;
+    overlay.clear()?;
+
+    Ok(FrameReport { status, hud, direction })
 }
 
-pub struct Config {
-    pub window_handle: HWND,
-    pub shellshock_handle: HWND,
-    pub dimensions: Size<u32>,
-    pub windows_objects: WindowsObjects,
+/// Parse the grid typed after `fan`, where each value left out is the default and `off` turns fan mode off.
+fn parse_fan(grid: &str) -> Option<Option<FanConfig>> {
+    if grid.trim() == "off" {
+        return Some(None)
+    }
+
+    let values: Vec<u8> = grid.split_ascii_whitespace().map(str::parse).collect::<Result<_, _>>().ok()?;
+    let default = FanConfig::default();
+    let value = |index: usize, default: u8| values.get(index).copied().unwrap_or(default);
+
+    Some(Some(FanConfig {
+        power_step: value(0, default.power_step),
+        angle_step: value(1, default.angle_step),
+        power_steps: value(2, default.power_steps),
+        angle_steps: value(3, default.angle_steps)
+    }))
 }
 
-pub fn event_loop(cfg: Config) -> Result<(), Box<dyn Error>> {
-    let screen_buffer = Bitmap::new_static(cfg.dimensions, 0.into());
-    let mut score_buffer = Bitmap::new_static(cfg.dimensions, 0.0);
+pub fn event_loop<S: FrameSource, O: OverlaySink>(mut cfg: Config<S, O>) -> Result<(), Box<dyn Error>> {
+    let mut screen_buffer = BitmapBuf::new(cfg.dimensions, 0.into());
+    let mut tracking = TrackingState::new(cfg.tracking);
 
-    let mut tank = Tank { screen_position: Coordinate(0, 0), angle: -77, power: 37, wind: 23, direction: Direction::Left };
+    let mut tank = Tank { physics: cfg.settings.physics, ..Tank::new(37, -77, 23, Direction::Left) };
 
     let (tank_sender, tank_receiver) = channel();
 
+    // typed values are used instead of those read from the HUD, until `hud` is typed
     let _thread_handle = thread::spawn(move || {
         let mut buffer = String::new();
         let stdin = io::stdin();
         let mut stdout = io::stdout();
 
+        let weapon_names: Vec<&str> = CATALOGUE.iter().map(|(name, _)| *name).collect();
+
         'input:
         loop {
             buffer.clear();
-            print!("Enter (power angle wind direction:(left: 0, right: 1) ):");
+            print!("Enter (power angle wind [direction:(left: 0, right: 1, automatic if left out)] ), a weapon ({}), (border stop/bounce/wrap) (fan [off | grid]) or (hud):", weapon_names.join(", "));
             let _ = stdout.flush();
 
             stdin.read_line(&mut buffer).unwrap();
+
+            if let Some(weapon) = Weapon::from_name(buffer.trim()) {
+                let _ = tank_sender.send(Input::Weapon(weapon));
+                continue 'input
+            }
+            if buffer.trim() == "hud" {
+                let _ = tank_sender.send(Input::Hud);
+                continue 'input
+            }
+            if let Some(grid) = buffer.trim().strip_prefix("fan") {
+                match parse_fan(grid) {
+                    Some(fan) => {
+                        let _ = tank_sender.send(Input::Fan(fan));
+                    },
+                    None => println!("Expected fan [off | power step [angle step [power steps [angle steps]]]]")
+                }
+                continue 'input
+            }
+            if let Some(mode) = buffer.trim().strip_prefix("border ") {
+                match Border::from_name(mode.trim()) {
+                    Some(border) => {
+                        let _ = tank_sender.send(Input::Border(border));
+                    },
+                    None => println!("Unknown border mode, expected stop, bounce or wrap")
+                }
+                continue 'input
+            }
+
             let tank_data_res: Result<Vec<i8>, ParseIntError> = buffer
                 .split_ascii_whitespace()
                 .take(4)
                 .map(|data| data.parse::<i8>())
                 .collect();
 
-            let tank = match tank_data_res {
+            let input = match tank_data_res {
                 Ok(tank_data) => {
-                    if tank_data.len() < 4 {
+                    if tank_data.len() < 3 {
                         println!("All values not entered");
                         continue 'input;
                     } else {
-                        let direction = if tank_data[3] == 0 { Direction::Left } else { Direction::Right };
-                        Tank { screen_position: Coordinate(0, 0), power: tank_data[0] as u8, angle: tank_data[1], wind: tank_data[2], direction }
+                        let direction = tank_data.get(3).map(|direction| if *direction == 0 { Direction::Left } else { Direction::Right });
+                        let tank = Tank::new(tank_data[0] as u8, tank_data[1], tank_data[2], direction.unwrap_or(Direction::Right));
+                        Input::Shot(tank, direction)
                     }
                 },
                 Err(_) => {
@@ -83,29 +289,253 @@ pub fn event_loop(cfg: Config) -> Result<(), Box<dyn Error>> {
                 }
             };
             
-            let _ = tank_sender.send(tank);
+            let _ = tank_sender.send(input);
         }
     });
 
-    // Main windows message pump
-    WindowsMessageLoop!(own_hwnd, LOOP_DURATION, {
-        if let Ok(new_tank) = tank_receiver.try_recv() {
-            tank = new_tank
-        }
+    if cfg.glyphs.is_none() {
+        println!("No glyph sheet is set, so the HUD isn't read. Type the power, angle and wind instead");
+    }
 
-        let screen_cap = screen_capture(cfg.shellshock_handle)?;
+    let mut last_hud_errors = Vec::new();
+    let mut options = FrameOptions {
+        style: cfg.settings.trajectory_style,
+        fan: cfg.fan,
+        glyphs: cfg.glyphs.clone(),
+        layout: cfg.settings.layout,
+        ..FrameOptions::default()
+    };
 
-        bitmap_bits_to_buffer(cfg.shellshock_handle, screen_cap, cfg.dimensions, screen_buffer.inner.as_mut_ptr())?;
+    // Main message pump
+    while cfg.overlay.handle_events() {
+        match tank_receiver.try_recv() {
+            Ok(Input::Shot(new_tank, new_direction)) => {
+                tank = Tank { direction: tank.direction, physics: tank.physics, weapon: tank.weapon, border: tank.border, ..new_tank };
+                options.direction_override = new_direction;
+                options.power_override = Some(new_tank.power);
+                options.angle_override = Some(new_tank.angle);
+                options.wind_override = Some(new_tank.wind);
+            },
+            Ok(Input::Hud) => {
+                options.power_override = None;
+                options.angle_override = None;
+                options.wind_override = None;
+            },
+            Ok(Input::Weapon(weapon)) => tank.weapon = weapon,
+            Ok(Input::Border(border)) => tank.border = border,
+            Ok(Input::Fan(fan)) => options.fan = fan,
+            Err(_) => {}
+        }
 
-        let location = find_tank(&screen_buffer, &mut score_buffer)
-            .ok_or_else(|| format!("Tank not found"))?;
+        let report = process_frame(&mut cfg.frame_source, &mut cfg.overlay, &mut screen_buffer, &mut tracking, &mut tank, &options)?;
 
-        tank.screen_position = location;
-        draw_tank_curve(cfg.window_handle, cfg.windows_objects.bitmap, cfg.dimensions, cfg.windows_objects.pen, &tank)?;
+        // only report unreadable values when they change, rather than every frame
+        let hud_errors = report.hud.map_or_else(Vec::new, |hud| hud.errors());
+        if hud_errors != last_hud_errors {
+            for error in &hud_errors {
+                eprintln!("{error}, using the last value");
+            }
+            last_hud_errors = hud_errors;
+        }
 
-        draw_bitmap(cfg.window_handle, cfg.windows_objects.bitmap, cfg.dimensions)?;
-        clear_bitmap(cfg.window_handle, cfg.windows_objects.bitmap, cfg.dimensions)?;
-    });
+        thread::sleep(cfg.settings.frame_interval);
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use crate::Coordinate;
+    use crate::bitmap::Bitmap;
+    use crate::impact::ImpactMarker;
+    use crate::weapon::ProjectilePath;
+
+    const DIMENSIONS: Size<u32> = Size(640, 360);
+
+    struct StillFrame {
+        tank_at: Option<Coordinate<usize>>
+    }
+
+    impl FrameSource for StillFrame {
+        type Error = Infallible;
+
+        fn dimensions(&self) -> Size<u32> {
+            DIMENSIONS
+        }
+
+        fn capture(&mut self, buffer: &mut Bitmap<ARGB>) -> Result<(), Infallible> {
+            let width = buffer.width;
+            let tank_at = self.tank_at;
+            buffer.fill_with(|i| {
+                let (x, y) = (i % width, i / width);
+                let on_tank = tank_at.is_some_and(|tank_at| (tank_at.0..tank_at.0+40).contains(&x) && (tank_at.1..tank_at.1+40).contains(&y));
+                if on_tank {
+                    ARGB { r: 0, g: 255, b: 0, a: 255 }
+                } else {
+                    ARGB { r: 40, g: 40, b: 40, a: 255 }
+                }
+            });
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingOverlay {
+        /// The start of each trajectory drawn
+        drawn: Vec<Coordinate<i32>>,
+        styles: Vec<TrajectoryStyle>,
+        texts: Vec<String>,
+        impacts: Vec<ImpactMarker>,
+        statuses: Vec<TrackingStatus>,
+        presented: usize
+    }
+
+    impl OverlaySink for RecordingOverlay {
+        type Error = Infallible;
+
+        fn draw_trajectory(&mut self, trajectory: &Trajectory, style: TrajectoryStyle) -> Result<(), Infallible> {
+            self.drawn.push(trajectory.points().next().unwrap());
+            self.styles.push(style);
+            Ok(())
+        }
+
+        fn draw_impact(&mut self, marker: &ImpactMarker) -> Result<(), Infallible> {
+            self.impacts.push(marker.clone());
+            Ok(())
+        }
+
+        fn draw_text(&mut self, text: &str, _position: Coordinate<i32>, _style: TextStyle) -> Result<(), Infallible> {
+            self.texts.push(text.to_string());
+            Ok(())
+        }
+
+        fn draw_status(&mut self, status: &TrackingStatus) -> Result<(), Infallible> {
+            self.statuses.push(*status);
+            Ok(())
+        }
+
+        fn present(&mut self) -> Result<(), Infallible> {
+            self.presented += 1;
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn process_frame_test() {
+        // deliberately the wrong size, to check the buffer is resized to the frame
+        let mut screen_buffer = BitmapBuf::new(Size(10, 10), 0.into());
+
+        let mut source = StillFrame { tank_at: Some(Coordinate(300, 200)) };
+        let mut overlay = RecordingOverlay::default();
+        let mut tracking = TrackingState::new(TrackingConfig::default());
+        let mut tank = Tank::new(50, 45, 0, Direction::Right);
+
+        process_frame(&mut source, &mut overlay, &mut screen_buffer, &mut tracking, &mut tank, &FrameOptions::default()).unwrap();
+
+        assert_eq!(overlay.presented, 1);
+        assert_eq!(screen_buffer.dimensions(), DIMENSIONS);
+        let Coordinate(x, y) = overlay.drawn[0];
+        assert!((300..340).contains(&x) && (200..240).contains(&y), "tank found at {x}, {y}");
+
+        // there is no terrain for the shot to land on, so it is marked where it leaves and only the tank's values are labelled
+        assert_eq!(overlay.impacts.len(), 1);
+        assert_eq!(overlay.impacts[0].blast_radius, None);
+        assert_eq!(overlay.texts, [tank_summary(&tank)]);
+        assert!(overlay.texts[0].starts_with("Power 50  Angle 45  Wind +0  Facing"), "{}", overlay.texts[0]);
+    }
+
+    #[test]
+    fn tank_hidden_test() {
+        let mut screen_buffer = BitmapBuf::new(DIMENSIONS, 0.into());
+
+        let mut source = StillFrame { tank_at: Some(Coordinate(300, 200)) };
+        let mut overlay = RecordingOverlay::default();
+        let mut tracking = TrackingState::new(TrackingConfig::default());
+        let mut tank = Tank::new(50, 45, 0, Direction::Right);
+
+        process_frame(&mut source, &mut overlay, &mut screen_buffer, &mut tracking, &mut tank, &FrameOptions::default()).unwrap();
+        source.tank_at = None;
+        let report = process_frame(&mut source, &mut overlay, &mut screen_buffer, &mut tracking, &mut tank, &FrameOptions::default()).unwrap();
+
+        // the second frame is drawn from the last known position
+        assert!(matches!(report.status, TrackingStatus::Holding { .. }));
+        assert_eq!(overlay.drawn.len(), 2);
+        assert_eq!(overlay.drawn[0], overlay.drawn[1]);
+        assert_eq!(overlay.statuses.len(), 2);
+        assert_eq!(overlay.presented, 2);
+    }
+
+    #[test]
+    fn fan_mode_test() {
+        let mut screen_buffer = BitmapBuf::new(DIMENSIONS, 0.into());
+
+        let mut source = StillFrame { tank_at: Some(Coordinate(300, 200)) };
+        let mut overlay = RecordingOverlay::default();
+        let mut tracking = TrackingState::new(TrackingConfig::default());
+        let mut tank = Tank::new(50, 45, 0, Direction::Right);
+        let options = FrameOptions { direction_override: Some(Direction::Right), fan: Some(FanConfig::default()), ..FrameOptions::default() };
+
+        process_frame(&mut source, &mut overlay, &mut screen_buffer, &mut tracking, &mut tank, &options).unwrap();
+
+        // a 3x3 grid, with the current shot drawn last in the normal style
+        assert_eq!(overlay.drawn.len(), 9);
+        assert_eq!(overlay.styles.last(), Some(&TrajectoryStyle::default()));
+        assert!(overlay.styles[..8].iter().all(|style| *style != TrajectoryStyle::default()));
+        assert_eq!(overlay.presented, 1);
+    }
+
+    #[test]
+    fn override_test() {
+        let mut screen_buffer = BitmapBuf::new(DIMENSIONS, 0.into());
+
+        let mut source = StillFrame { tank_at: Some(Coordinate(300, 200)) };
+        let mut overlay = RecordingOverlay::default();
+        let mut tracking = TrackingState::new(TrackingConfig::default());
+        let mut tank = Tank::new(50, 45, 0, Direction::Right);
+        let options = FrameOptions { power_override: Some(80), angle_override: Some(-77), wind_override: Some(-12), ..FrameOptions::default() };
+
+        process_frame(&mut source, &mut overlay, &mut screen_buffer, &mut tracking, &mut tank, &options).unwrap();
+
+        assert_eq!((tank.power, tank.angle, tank.wind), (80, -77, -12));
+    }
+
+    #[test]
+    fn hud_is_not_read_without_glyphs_test() {
+        let mut screen_buffer = BitmapBuf::new(DIMENSIONS, 0.into());
+
+        let mut source = StillFrame { tank_at: Some(Coordinate(300, 200)) };
+        let mut overlay = RecordingOverlay::default();
+        let mut tracking = TrackingState::new(TrackingConfig::default());
+        let mut tank = Tank::new(50, 45, 0, Direction::Right);
+
+        let report = process_frame(&mut source, &mut overlay, &mut screen_buffer, &mut tracking, &mut tank, &FrameOptions::default()).unwrap();
+
+        assert_eq!(report.hud, None);
+        assert_eq!((tank.power, tank.angle, tank.wind), (50, 45, 0));
+    }
+
+    #[test]
+    fn impact_label_test() {
+        let tank = Tank::new(70, 45, 0, Direction::Right);
+        let landing_at = |x: i32, y: i32| Trajectory::from_paths(vec![ProjectilePath::through(&[(x, y)], true, 1.0)]);
+        let dimensions = Size(200, 100);
+
+        assert_eq!(impact_label(&tank, &landing_at(20, 20), dimensions), Some(("P70 A45".to_string(), Coordinate(28, 28))));
+        assert_eq!(impact_label(&tank, &Trajectory::default(), dimensions), None);
+    }
+
+    #[test]
+    fn parse_fan_test() {
+        assert_eq!(parse_fan(""), Some(Some(FanConfig::default())));
+        assert_eq!(parse_fan(" off"), Some(None));
+        assert_eq!(parse_fan(" 5 3"), Some(Some(FanConfig { power_step: 5, angle_step: 3, ..FanConfig::default() })));
+        assert_eq!(parse_fan(" five"), None);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::Coordinate;
+use crate::bitmap::ARGB;
+use crate::draw::TrajectoryStyle;
+use crate::solver::{ANGLE_RANGE, POWER_RANGE};
+use crate::tank::Tank;
+use crate::terrain::Terrain;
+use crate::trajectory::Trajectory;
+use crate::viewport::Viewport;
+
+/// The colours neighbouring trajectories with less and more power are graded towards.
+const LESS_POWER_COLOUR: ARGB = ARGB { r: 90, g: 160, b: 255, a: 255 };
+const MORE_POWER_COLOUR: ARGB = ARGB { r: 255, g: 210, b: 70, a: 255 };
+/// The opacity of the nearest and furthest neighbouring trajectories.
+const NEAREST_ALPHA: u8 = 200;
+const FURTHEST_ALPHA: u8 = 70;
+
+/// The grid of neighbouring power and angle values drawn around the current shot in fan mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FanConfig {
+    pub power_step: u8,
+    /// How many steps of power are drawn either side of the current power
+    pub power_steps: u8,
+    /// In degrees
+    pub angle_step: u8,
+    /// How many steps of angle are drawn either side of the current angle
+    pub angle_steps: u8
+}
+
+impl Default for FanConfig {
+    fn default() -> Self {
+        Self { power_step: 1, power_steps: 1, angle_step: 2, angle_steps: 1 }
+    }
+}
+
+/// One of the trajectories in a fan.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FanMember {
+    pub power: u8,
+    pub angle: i8,
+    /// Whether this is the current shot, rather than a neighbour
+    pub selected: bool,
+    pub style: TrajectoryStyle,
+    pub trajectory: Trajectory
+}
+
+fn blend(from: ARGB, to: ARGB, fraction: f32) -> ARGB {
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * fraction).round() as u8;
+    ARGB { r: channel(from.r, to.r), g: channel(from.g, to.g), b: channel(from.b, to.b), a: channel(from.a, to.a) }
+}
+
+/// The style of the trajectory `power_offset` and `angle_offset` steps away from the current shot, which is drawn with `selected`.
+///
+/// The colour is graded by the power, and the opacity by how far away it is in the grid.
+pub fn neighbour_style(config: &FanConfig, selected: TrajectoryStyle, power_offset: i16, angle_offset: i16) -> TrajectoryStyle {
+    if power_offset == 0 && angle_offset == 0 {
+        return selected
+    }
+
+    let power_fraction = if config.power_steps == 0 { 0.0 } else { power_offset as f32 / config.power_steps as f32 };
+    let towards = if power_fraction < 0.0 { LESS_POWER_COLOUR } else { MORE_POWER_COLOUR };
+    let colour = blend(selected.colour, towards, power_fraction.abs());
+
+    let ring = power_offset.abs().max(angle_offset.abs());
+    let rings = config.power_steps.max(config.angle_steps) as i16;
+    let distance = if rings <= 1 { 0.0 } else { (ring - 1) as f32 / (rings - 1) as f32 };
+    let alpha = NEAREST_ALPHA as f32 - (NEAREST_ALPHA - FURTHEST_ALPHA) as f32 * distance;
+
+    TrajectoryStyle { colour: ARGB { a: alpha.round() as u8, ..colour }, width: (selected.width - 1).max(1), ..selected }
+}
+
+/// The trajectories of every shot in the `config` grid around `tank`'s power and angle,
+/// leaving out those with a power or angle that can't be selected in game.
+///
+/// The furthest neighbours come first and the current shot last, so drawing them in order leaves the current shot on top.
+/// The current shot is drawn with `selected_style`, and the neighbours fainter, see `neighbour_style`.
+pub fn fan(tank: &Tank, viewport: &Viewport, terrain: Option<&Terrain>, targets: &[Coordinate<u32>], config: &FanConfig, selected_style: TrajectoryStyle) -> Vec<FanMember> {
+    let (power_steps, angle_steps) = (config.power_steps as i16, config.angle_steps as i16);
+    let mut offsets: Vec<(i16, i16)> = (-power_steps..=power_steps)
+        .flat_map(|power_offset| (-angle_steps..=angle_steps).map(move |angle_offset| (power_offset, angle_offset)))
+        .collect();
+    offsets.sort_by_key(|(power_offset, angle_offset)| std::cmp::Reverse(power_offset.abs().max(angle_offset.abs())));
+
+    offsets.into_iter().filter_map(|(power_offset, angle_offset)| {
+        let power = tank.power as i16 + power_offset * config.power_step as i16;
+        let angle = tank.angle as i16 + angle_offset * config.angle_step as i16;
+        let selected = power_offset == 0 && angle_offset == 0;
+
+        let in_range = u8::try_from(power).is_ok_and(|power| POWER_RANGE.contains(&power))
+            && i8::try_from(angle).is_ok_and(|angle| ANGLE_RANGE.contains(&angle));
+        if !in_range && !selected {
+            return None
+        }
+
+        let neighbour = Tank { power: power as u8, angle: angle as i8, ..tank.clone() };
+        Some(FanMember {
+            power: neighbour.power,
+            angle: neighbour.angle,
+            selected,
+            style: neighbour_style(config, selected_style, power_offset, angle_offset),
+            trajectory: neighbour.trajectory(viewport, terrain, targets)
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Size;
+    use crate::viewport::Layout;
+    use crate::draw::TRAJECTORY_COLOUR;
+    use crate::tank::Direction;
+
+    const DIMENSIONS: Size<u32> = Size(2560, 1440);
+
+    fn viewport() -> Viewport {
+        Viewport::from_dimensions(DIMENSIONS, Layout::default())
+    }
+
+    fn tank(power: u8, angle: i8) -> Tank {
+        Tank { screen_position: Coordinate(300, 400), ..Tank::new(power, angle, 0, Direction::Right) }
+    }
+
+    #[test]
+    fn fan_test() {
+        let config = FanConfig { power_step: 1, power_steps: 2, angle_step: 2, angle_steps: 1 };
+        let members = fan(&tank(50, 40), &viewport(), None, &[], &config, TrajectoryStyle::default());
+
+        assert_eq!(members.len(), 15);
+        let selected = members.last().unwrap();
+        assert!(selected.selected && selected.power == 50 && selected.angle == 40);
+        assert_eq!(selected.style, TrajectoryStyle::default());
+        assert_eq!(selected.trajectory, tank(50, 40).trajectory(&viewport(), None, &[]));
+
+        let corner = members.iter().find(|member| member.power == 48 && member.angle == 38).unwrap();
+        let nearest = members.iter().find(|member| member.power == 51 && member.angle == 40).unwrap();
+        assert_eq!(corner.trajectory, tank(48, 38).trajectory(&viewport(), None, &[]));
+        assert_eq!((corner.style.colour.a, nearest.style.colour.a), (FURTHEST_ALPHA, NEAREST_ALPHA));
+        assert_eq!(corner.style.colour.b, LESS_POWER_COLOUR.b);
+        assert!(nearest.style.colour.g > TRAJECTORY_COLOUR.g);
+
+        // drawn from the outside in
+        assert!(members.windows(2).all(|pair| pair[0].style.colour.a <= pair[1].style.colour.a || pair[1].selected));
+    }
+
+    #[test]
+    fn fan_range_test() {
+        let members = fan(&tank(100, 90), &viewport(), None, &[], &FanConfig::default(), TrajectoryStyle::default());
+
+        // more power or a steeper angle can't be selected
+        assert_eq!(members.len(), 4);
+        assert!(members.iter().all(|member| member.power <= 100 && member.angle <= 90));
+    }
+
+    #[test]
+    fn fan_negative_angle_test() {
+        // aiming below level, every neighbour can be selected
+        let members = fan(&tank(37, -77), &viewport(), None, &[], &FanConfig::default(), TrajectoryStyle::default());
+
+        assert_eq!(members.len(), 9);
+        let angles: Vec<i8> = members.iter().filter(|member| member.power == 37).map(|member| member.angle).collect();
+        assert!([-79, -77, -75].iter().all(|angle| angles.contains(angle)), "{angles:?}");
+
+        // but not past straight down
+        let members = fan(&tank(37, -89), &viewport(), None, &[], &FanConfig::default(), TrajectoryStyle::default());
+        assert_eq!(members.len(), 6);
+        assert!(members.iter().all(|member| member.angle >= -90));
+    }
+}
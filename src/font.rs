@@ -0,0 +1,270 @@
+use crate::{Coordinate, Size};
+use crate::bitmap::{Bitmap, ARGB};
+use crate::draw::blend_at;
+
+/// Glyphs are this many pixels wide and tall before scaling, see `TextStyle::scale`.
+pub const GLYPH_SIZE: Size<u32> = Size(5, 7);
+/// The space between characters, and between lines, before scaling.
+const CHARACTER_SPACING: u32 = 1;
+const LINE_SPACING: u32 = 2;
+
+/// The printable ASCII characters, drawn on a 5x7 grid from the top row down.
+const FONT: [(char, [&str; 7]); 95] = [
+    (' ', [".....", ".....", ".....", ".....", ".....", ".....", "....."]),
+    ('!', ["..#..", "..#..", "..#..", "..#..", "..#..", ".....", "..#.."]),
+    ('"', [".#.#.", ".#.#.", ".#.#.", ".....", ".....", ".....", "....."]),
+    ('#', [".#.#.", ".#.#.", "#####", ".#.#.", "#####", ".#.#.", ".#.#."]),
+    ('$', ["..#..", ".####", "#.#..", ".###.", "..#.#", "####.", "..#.."]),
+    ('%', ["##...", "##..#", "...#.", "..#..", ".#...", "#..##", "...##"]),
+    ('&', [".##..", "#..#.", "#.#..", ".#...", "#.#.#", "#..#.", ".##.#"]),
+    ('\'', [".##..", "..#..", ".#...", ".....", ".....", ".....", "....."]),
+    ('(', ["...#.", "..#..", ".#...", ".#...", ".#...", "..#..", "...#."]),
+    (')', [".#...", "..#..", "...#.", "...#.", "...#.", "..#..", ".#..."]),
+    ('*', [".....", ".#.#.", "..#..", "#####", "..#..", ".#.#.", "....."]),
+    ('+', [".....", "..#..", "..#..", "#####", "..#..", "..#..", "....."]),
+    (',', [".....", ".....", ".....", ".....", ".##..", "..#..", ".#..."]),
+    ('-', [".....", ".....", ".....", "#####", ".....", ".....", "....."]),
+    ('.', [".....", ".....", ".....", ".....", ".....", ".##..", ".##.."]),
+    ('/', [".....", "....#", "...#.", "..#..", ".#...", "#....", "....."]),
+    ('0', [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."]),
+    ('1', ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('2', [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"]),
+    ('3', ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."]),
+    ('4', ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."]),
+    ('5', ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."]),
+    ('6', ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."]),
+    ('7', ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]),
+    ('8', [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."]),
+    ('9', [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."]),
+    (':', [".....", ".##..", ".##..", ".....", ".##..", ".##..", "....."]),
+    (';', [".....", ".##..", ".##..", ".....", ".##..", "..#..", ".#..."]),
+    ('<', ["....#", "...#.", "..#..", ".#...", "..#..", "...#.", "....#"]),
+    ('=', [".....", ".....", "#####", ".....", "#####", ".....", "....."]),
+    ('>', ["#....", ".#...", "..#..", "...#.", "..#..", ".#...", "#...."]),
+    ('?', [".###.", "#...#", "....#", "...#.", "..#..", ".....", "..#.."]),
+    ('@', [".###.", "#...#", "....#", ".##.#", "#.#.#", "#.#.#", ".###."]),
+    ('A', [".###.", "#...#", "#...#", "#...#", "#####", "#...#", "#...#"]),
+    ('B', ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."]),
+    ('C', [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."]),
+    ('D', ["###..", "#..#.", "#...#", "#...#", "#...#", "#..#.", "###.."]),
+    ('E', ["#####", "#....", "#....", "####.", "#....", "#....", "#####"]),
+    ('F', ["#####", "#....", "#....", "###..", "#....", "#....", "#...."]),
+    ('G', [".###.", "#...#", "#....", "#....", "#..##", "#...#", ".###."]),
+    ('H', ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "#....", "#####"]),
+    ('M', ["#...#", "##.##", "#.#.#", "#...#", "#...#", "#...#", "#...#"]),
+    ('N', ["#...#", "#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#"]),
+    ('O', [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('P', ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."]),
+    ('Q', [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"]),
+    ('R', ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"]),
+    ('S', [".####", "#....", "#....", ".###.", "....#", "....#", "####."]),
+    ('T', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('U', ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('V', ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('W', ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "##.##", "#...#"]),
+    ('X', ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."]),
+    ('Z', ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"]),
+    ('[', ["..###", "..#..", "..#..", "..#..", "..#..", "..#..", "..###"]),
+    ('\\', [".....", "#....", ".#...", "..#..", "...#.", "....#", "....."]),
+    (']', ["###..", "..#..", "..#..", "..#..", "..#..", "..#..", "###.."]),
+    ('^', ["..#..", ".#.#.", "#...#", ".....", ".....", ".....", "....."]),
+    ('_', [".....", ".....", ".....", ".....", ".....", ".....", "#####"]),
+    ('`', [".#...", "..#..", "...#.", ".....", ".....", ".....", "....."]),
+    ('a', [".....", ".....", ".###.", "....#", ".####", "#...#", ".####"]),
+    ('b', ["#....", "#....", "#.##.", "##..#", "#...#", "#...#", "####."]),
+    ('c', [".....", ".....", ".###.", "#....", "#....", "#...#", ".###."]),
+    ('d', ["....#", "....#", ".##.#", "#..##", "#...#", "#...#", ".####"]),
+    ('e', [".....", ".....", ".###.", "#...#", "#####", "#....", ".###."]),
+    ('f', ["..##.", ".#..#", ".#...", "###..", ".#...", ".#...", ".#..."]),
+    ('g', [".....", ".....", ".####", "#...#", ".####", "....#", "..##."]),
+    ('h', ["#....", "#....", "#.##.", "##..#", "#...#", "#...#", "#...#"]),
+    ('i', ["..#..", ".....", ".##..", "..#..", "..#..", "..#..", ".###."]),
+    ('j', ["...#.", ".....", "..##.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('k', [".#...", ".#...", ".#..#", ".#.#.", ".##..", ".#.#.", ".#..#"]),
+    ('l', [".##..", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('m', [".....", ".....", "##.#.", "#.#.#", "#.#.#", "#...#", "#...#"]),
+    ('n', [".....", ".....", "#.##.", "##..#", "#...#", "#...#", "#...#"]),
+    ('o', [".....", ".....", ".###.", "#...#", "#...#", "#...#", ".###."]),
+    ('p', [".....", ".....", "####.", "#...#", "####.", "#....", "#...."]),
+    ('q', [".....", ".....", ".##.#", "#..##", ".####", "....#", "....#"]),
+    ('r', [".....", ".....", "#.##.", "##..#", "#....", "#....", "#...."]),
+    ('s', [".....", ".....", ".###.", "#....", ".###.", "....#", "####."]),
+    ('t', [".#...", ".#...", "###..", ".#...", ".#...", ".#..#", "..##."]),
+    ('u', [".....", ".....", "#...#", "#...#", "#...#", "#..##", ".##.#"]),
+    ('v', [".....", ".....", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('w', [".....", ".....", "#...#", "#...#", "#.#.#", "#.#.#", ".#.#."]),
+    ('x', [".....", ".....", "#...#", ".#.#.", "..#..", ".#.#.", "#...#"]),
+    ('y', [".....", ".....", "#...#", "#...#", ".####", "....#", ".###."]),
+    ('z', [".....", ".....", "#####", "...#.", "..#..", ".#...", "#####"]),
+    ('{', ["...#.", "..#..", "..#..", ".#...", "..#..", "..#..", "...#."]),
+    ('|', ["..#..", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('}', [".#...", "..#..", "..#..", "...#.", "..#..", "..#..", ".#..."]),
+    ('~', [".....", ".....", ".#...", "#.#.#", "...#.", ".....", "....."]),
+];
+
+/// Drawn in place of characters that aren't in the font.
+const REPLACEMENT: char = '?';
+
+/// How text is made readable over the game's art.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextEffect {
+    None,
+    /// A one pixel border all around the text
+    Outline(ARGB),
+    /// A copy of the text one pixel down and to the right, behind it
+    Shadow(ARGB)
+}
+
+/// How text is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    pub colour: ARGB,
+    pub effect: TextEffect,
+    /// How many pixels wide and tall each cell of a glyph is
+    pub scale: u32
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            colour: ARGB { r: 255, g: 255, b: 255, a: 255 },
+            effect: TextEffect::Outline(ARGB { r: 0, g: 0, b: 0, a: 220 }),
+            scale: 2
+        }
+    }
+}
+
+/// The 5x7 grid of `character`, from the top row down.
+pub(crate) fn glyph_rows(character: char) -> &'static [&'static str; 7] {
+    let index = |character: char| (character as usize).wrapping_sub(' ' as usize);
+    let (_, rows) = FONT.get(index(character)).unwrap_or(&FONT[index(REPLACEMENT)]);
+    rows
+}
+
+/// The size of `text` when drawn at `scale`, not including any effect. Lines are separated by `\n`.
+pub fn text_size(text: &str, scale: u32) -> Size<u32> {
+    let lines = text.split('\n').count() as u32;
+    let longest = text.split('\n').map(|line| line.chars().count() as u32).max().unwrap_or(0);
+    if longest == 0 {
+        return Size(0, lines * (GLYPH_SIZE.1 + LINE_SPACING) * scale - LINE_SPACING * scale)
+    }
+
+    Size(
+        (longest * (GLYPH_SIZE.0 + CHARACTER_SPACING) - CHARACTER_SPACING) * scale,
+        (lines * (GLYPH_SIZE.1 + LINE_SPACING) - LINE_SPACING) * scale
+    )
+}
+
+/// Draw `text`, which may have several lines, with the bottom-left corner of the block at `position`.
+///
+/// Coordinates are relative to bottom-left, and any of the text outside the bitmap is ignored.
+pub fn draw_text(bitmap: &mut Bitmap<ARGB>, text: &str, position: Coordinate<i32>, style: TextStyle) {
+    let size = text_size(text, style.scale);
+    // the cells covered by the text, from the top row down, with a pixel of margin all around for the effect
+    let mask_width = size.0 as usize + 2;
+    let mut mask = vec![false; mask_width * (size.1 as usize + 2)];
+
+    let scale = style.scale as usize;
+    for (line_index, line) in text.split('\n').enumerate() {
+        let line_top = 1 + line_index * (GLYPH_SIZE.1 + LINE_SPACING) as usize * scale;
+
+        for (character_index, character) in line.chars().enumerate() {
+            let left = 1 + character_index * (GLYPH_SIZE.0 + CHARACTER_SPACING) as usize * scale;
+
+            for (row, cells) in glyph_rows(character).iter().enumerate() {
+                for (column, _) in cells.chars().enumerate().filter(|(_, cell)| *cell == '#') {
+                    for y in line_top + row*scale..line_top + (row + 1)*scale {
+                        let start = y*mask_width + left + column*scale;
+                        mask[start..start + scale].fill(true);
+                    }
+                }
+            }
+        }
+    }
+
+    let lit = |x: usize, y: usize| mask[y*mask_width + x];
+    let height = mask.len() / mask_width;
+    for y in 0..height {
+        for x in 0..mask_width {
+            let colour = if lit(x, y) {
+                style.colour
+            } else {
+                let behind = match style.effect {
+                    TextEffect::None => false,
+                    TextEffect::Outline(_) => (y.saturating_sub(1)..=(y + 1).min(height - 1))
+                        .any(|y| (x.saturating_sub(1)..=(x + 1).min(mask_width - 1)).any(|x| lit(x, y))),
+                    TextEffect::Shadow(_) => x > 0 && y > 0 && lit(x - 1, y - 1)
+                };
+                match style.effect {
+                    TextEffect::Outline(colour) | TextEffect::Shadow(colour) if behind => colour,
+                    _ => continue
+                }
+            };
+
+            // the mask's top row is a pixel above the text, and its bottom row a pixel below
+            blend_at(bitmap, position.0 - 1 + x as i32, position.1 + size.1 as i32 - y as i32, colour, 1.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: ARGB = ARGB { r: 255, g: 255, b: 255, a: 255 };
+    const BLACK: ARGB = ARGB { r: 0, g: 0, b: 0, a: 255 };
+
+    /// The bitmap as rows of `#` for text, `o` for the effect and `.` for anything else, from the top row down.
+    fn rows(pixels: &[ARGB], width: usize) -> Vec<String> {
+        pixels.chunks(width).rev()
+            .map(|row| row.iter().map(|pixel| match *pixel {
+                WHITE => '#',
+                BLACK => 'o',
+                _ => '.'
+            }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn text_size_test() {
+        assert_eq!(text_size("A", 1), GLYPH_SIZE);
+        assert_eq!(text_size("Hi!", 2), Size(34, 14));
+        assert_eq!(text_size("Power\n1", 1), Size(29, 16));
+        assert_eq!(glyph_rows('\u{b0}'), glyph_rows('?'));
+    }
+
+    #[test]
+    fn draw_text_test() {
+        let mut pixels = [ARGB::from(0); 8*10];
+        let mut bitmap = Bitmap::new(&mut pixels, 8);
+        let style = TextStyle { colour: WHITE, effect: TextEffect::Outline(BLACK), scale: 1 };
+
+        draw_text(&mut bitmap, "1", Coordinate(1, 1), style);
+
+        assert_eq!(rows(&pixels, 8), [
+            "........",
+            "..ooo...",
+            ".oo#o...",
+            ".o##o...",
+            ".oo#o...",
+            "..o#o...",
+            "..o#o...",
+            ".oo#oo..",
+            ".o###o..",
+            ".ooooo..",
+        ]);
+
+        let mut pixels = [ARGB::from(0); 8*10];
+        let mut bitmap = Bitmap::new(&mut pixels, 8);
+        let style = TextStyle { effect: TextEffect::Shadow(BLACK), ..style };
+
+        draw_text(&mut bitmap, "1", Coordinate(1, 1), style);
+
+        assert_eq!(rows(&pixels, 8)[7..], ["...#o...", "..###...", "...ooo.."]);
+    }
+}
@@ -0,0 +1,453 @@
+use std::ops::RangeInclusive;
+
+use thiserror::Error;
+
+use crate::{Coordinate, Rect, Size};
+use crate::bitmap::{Bitmap, ARGB};
+use crate::font;
+use crate::solver::{ANGLE_RANGE, POWER_RANGE};
+use crate::tank::{Direction, Tank};
+use crate::viewport::Viewport;
+
+/// Pixels at least this bright are considered part of the HUD's text.
+pub const TEXT_LUMINANCE: f32 = 200.0;
+/// The fraction of template cells a glyph must match to be recognised.
+pub const MIN_GLYPH_SIMILARITY: f32 = 0.85;
+/// Groups of fewer lit pixels than this are ignored as noise.
+const MIN_GLYPH_PIXELS: usize = 4;
+
+// Where the readouts are has only been estimated from screenshots, and not yet checked against captures of the game,
+// see `tests/captures.rs`. Until then, the HUD is only read when a glyph sheet is set, see `FrameOptions::glyphs`.
+
+/// The area of the wind indicator, at the top centre of the screen.
+/// Fractions of the game area, relative to bottom left.
+pub const WIND_REGION: HudRegion = HudRegion { from: (0.44, 0.91), to: (0.56, 0.98) };
+/// The area of the power readout, in the menu bar.
+pub const POWER_REGION: HudRegion = HudRegion { from: (0.33, 0.03), to: (0.42, 0.10) };
+/// The area of the angle readout, in the menu bar.
+pub const ANGLE_REGION: HudRegion = HudRegion { from: (0.58, 0.03), to: (0.67, 0.10) };
+
+/// The characters of a glyph sheet from left to right, see `GlyphSet::from_sheet`.
+/// `-` is the minus sign of negative angles, and `<` and `>` are the wind arrows pointing left and right.
+pub const SHEET_CHARACTERS: &str = "0123456789-<>";
+
+/// An area of the HUD, as fractions of the game area relative to bottom left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HudRegion {
+    pub from: (f32, f32),
+    pub to: (f32, f32)
+}
+
+impl HudRegion {
+    pub fn to_rect(self, viewport: &Viewport) -> Rect {
+        viewport.region(self.from, self.to)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Glyph {
+    Digit(u8),
+    Minus,
+    Arrow(Direction)
+}
+
+impl Glyph {
+    /// The glyph written as `character` in `SHEET_CHARACTERS`.
+    pub fn from_char(character: char) -> Option<Self> {
+        match character {
+            '-' => Some(Glyph::Minus),
+            '<' => Some(Glyph::Arrow(Direction::Left)),
+            '>' => Some(Glyph::Arrow(Direction::Right)),
+            _ => character.to_digit(10).map(|digit| Glyph::Digit(digit as u8))
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum HudError {
+    #[error("No {0} readout found on screen")]
+    Missing(&'static str),
+    #[error("Unrecognised character in the {field} readout (best match {similarity:.2})")]
+    Unrecognised { field: &'static str, similarity: f32 },
+    #[error("The {0} readout isn't in the expected format")]
+    Malformed(&'static str),
+    #[error("The glyph sheet has {found} characters, expected {expected}")]
+    GlyphSheet { found: usize, expected: usize }
+}
+
+/// The wind shown on the HUD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wind {
+    pub strength: u8,
+    /// `None` when there is no wind
+    pub direction: Option<Direction>
+}
+
+impl Wind {
+    /// The wind as used by `Tank::wind`, which is positive when blowing the way the tank is firing.
+    pub fn relative_to(self, firing: Direction) -> i8 {
+        let strength = self.strength.min(i8::MAX as u8) as i8;
+        match self.direction {
+            Some(direction) if direction == firing => strength,
+            Some(_) => -strength,
+            None => 0
+        }
+    }
+}
+
+/// A template cropped to its ink, so glyphs can be compared regardless of the space around them.
+#[derive(Clone, Debug, PartialEq)]
+struct Template {
+    glyph: Glyph,
+    /// From the top row down
+    cells: Vec<bool>,
+    size: Size<usize>
+}
+
+/// The templates characters on the HUD are recognised with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphSet {
+    templates: Vec<Template>
+}
+
+impl Default for GlyphSet {
+    /// Templates from the overlay's own font, which only roughly resemble the game's.
+    /// Load a set from a capture of the game's HUD with `from_sheet` to read it reliably.
+    fn default() -> Self {
+        let templates = SHEET_CHARACTERS.chars().map(|character| {
+            // the arrows are drawn as the angle brackets
+            let rows = font::glyph_rows(character);
+            let lit = |x: usize, y: usize| rows[y].as_bytes()[x] == b'#';
+
+            let columns: Vec<usize> = (0..5).filter(|x| (0..7).any(|y| lit(*x, y))).collect();
+            let rows: Vec<usize> = (0..7).filter(|y| (0..5).any(|x| lit(x, *y))).collect();
+            let (left, right) = (columns[0], columns[columns.len() - 1]);
+            let (top, bottom) = (rows[0], rows[rows.len() - 1]);
+
+            let cells = (top..=bottom).flat_map(|y| (left..=right).map(move |x| lit(x, y))).collect();
+            let size = Size(right - left + 1, bottom - top + 1);
+            Template { glyph: Glyph::from_char(character).expect("every sheet character is a glyph"), cells, size }
+        }).collect();
+
+        Self { templates }
+    }
+}
+
+impl GlyphSet {
+    /// Derive the templates from a capture of the game's own characters, such as the HUD's readouts cropped and placed side by side.
+    ///
+    /// `region` must contain exactly the characters of `SHEET_CHARACTERS`, in that order from left to right, as text bright enough to read.
+    pub fn from_sheet(bitmap: &Bitmap<ARGB>, region: Rect) -> Result<Self, HudError> {
+        let bounds = segment(bitmap, region);
+        let expected = SHEET_CHARACTERS.chars().count();
+        if bounds.len() != expected {
+            return Err(HudError::GlyphSheet { found: bounds.len(), expected })
+        }
+
+        let templates = SHEET_CHARACTERS.chars().zip(bounds).map(|(character, bounds)| {
+            let cells = (0..bounds.size.1).rev()
+                .flat_map(|row| (0..bounds.size.0).map(move |col| (col, row)))
+                .map(|(col, row)| is_text(bitmap.inner[(bounds.origin.1 + row)*bitmap.width + bounds.origin.0 + col]))
+                .collect();
+
+            Template { glyph: Glyph::from_char(character).expect("every sheet character is a glyph"), cells, size: bounds.size }
+        }).collect();
+
+        Ok(Self { templates })
+    }
+}
+
+fn is_text(pixel: ARGB) -> bool {
+    0.299*pixel.r as f32 + 0.587*pixel.g as f32 + 0.114*pixel.b as f32 >= TEXT_LUMINANCE
+}
+
+/// Splits the text in `region` into the bounding boxes of each character, from left to right.
+fn segment(bitmap: &Bitmap<ARGB>, region: Rect) -> Vec<Rect> {
+    let lit = |x: usize, y: usize| is_text(bitmap.inner[y*bitmap.width + x]);
+    let column_lit = |x: usize| (region.origin.1..region.end().1).any(|y| lit(x, y));
+
+    let mut boxes = Vec::new();
+    let mut x = region.origin.0;
+
+    while x < region.end().0 {
+        if !column_lit(x) {
+            x += 1;
+            continue
+        }
+
+        let left = x;
+        while x < region.end().0 && column_lit(x) {
+            x += 1;
+        }
+
+        let lit_rows: Vec<usize> = (region.origin.1..region.end().1).filter(|y| (left..x).any(|col| lit(col, *y))).collect();
+        let pixels = (left..x).flat_map(|col| lit_rows.iter().map(move |y| (col, *y))).filter(|(col, y)| lit(*col, *y)).count();
+
+        if pixels >= MIN_GLYPH_PIXELS {
+            let (bottom, top) = (lit_rows[0], lit_rows[lit_rows.len() - 1]);
+            boxes.push(Rect { origin: Coordinate(left, bottom), size: Size(x - left, top - bottom + 1) });
+        }
+    }
+
+    boxes
+}
+
+/// Compares the character in `bounds` against every template, returning the best match and its similarity.
+fn recognise(bitmap: &Bitmap<ARGB>, bounds: Rect, templates: &[Template]) -> (Glyph, f32) {
+    let aspect = bounds.size.0 as f32 / bounds.size.1 as f32;
+
+    templates.iter().map(|template| {
+        let template_aspect = template.size.0 as f32 / template.size.1 as f32;
+        if aspect / template_aspect > 1.6 || template_aspect / aspect > 1.6 {
+            return (template.glyph, 0.0)
+        }
+
+        let matching = template.cells.iter().enumerate().filter(|(i, cell)| {
+            let (col, row) = (i % template.size.0, i / template.size.0);
+            // sample the centre of the cell, with rows counted down from the top
+            let x = bounds.origin.0 + ((col as f32 + 0.5) * bounds.size.0 as f32 / template.size.0 as f32) as usize;
+            let y = bounds.end().1 - 1 - ((row as f32 + 0.5) * bounds.size.1 as f32 / template.size.1 as f32) as usize;
+
+            is_text(bitmap.inner[y*bitmap.width + x]) == **cell
+        }).count();
+
+        (template.glyph, matching as f32 / template.cells.len() as f32)
+    }).fold((Glyph::Digit(0), f32::MIN), |best, current| if current.1 > best.1 { current } else { best })
+}
+
+/// Recognise every character in `region`, from left to right.
+///
+/// Fails rather than guessing if any character doesn't closely match a glyph.
+pub fn read_glyphs(bitmap: &Bitmap<ARGB>, glyphs: &GlyphSet, region: Rect, field: &'static str) -> Result<Vec<Glyph>, HudError> {
+    let bounds = Rect { origin: Coordinate(0, 0), size: Size(bitmap.width, bitmap.height()) };
+    let region = region.intersection(&bounds).ok_or(HudError::Missing(field))?;
+
+    let glyphs = segment(bitmap, region).into_iter().map(|bounds| {
+        let (glyph, similarity) = recognise(bitmap, bounds, &glyphs.templates);
+        if similarity >= MIN_GLYPH_SIMILARITY {
+            Ok(glyph)
+        } else {
+            Err(HudError::Unrecognised { field, similarity })
+        }
+    }).collect::<Result<Vec<_>, _>>()?;
+
+    if glyphs.is_empty() {
+        return Err(HudError::Missing(field))
+    }
+
+    Ok(glyphs)
+}
+
+/// Parse a run of digit glyphs into a number.
+fn parse_number(glyphs: &[Glyph], field: &'static str) -> Result<u32, HudError> {
+    if glyphs.is_empty() || glyphs.len() > 3 {
+        return Err(HudError::Malformed(field))
+    }
+
+    glyphs.iter().try_fold(0, |number, glyph| match glyph {
+        Glyph::Digit(digit) => Ok(number*10 + *digit as u32),
+        Glyph::Minus | Glyph::Arrow(_) => Err(HudError::Malformed(field))
+    })
+}
+
+/// Parse a run of digit glyphs, which may start with a minus sign, into a number.
+fn parse_signed(glyphs: &[Glyph], field: &'static str) -> Result<i32, HudError> {
+    match glyphs.split_first() {
+        Some((Glyph::Minus, digits)) => parse_number(digits, field).map(|number| -(number as i32)),
+        _ => parse_number(glyphs, field).map(|number| number as i32)
+    }
+}
+
+/// Read the wind strength and the direction of its arrow from the HUD.
+pub fn read_wind(bitmap: &Bitmap<ARGB>, glyphs: &GlyphSet, viewport: &Viewport) -> Result<Wind, HudError> {
+    let region = WIND_REGION.to_rect(viewport);
+    let glyphs = read_glyphs(bitmap, glyphs, region, "wind")?;
+
+    // the arrow can be on either side of the strength
+    let direction = match (glyphs.first(), glyphs.last()) {
+        (Some(Glyph::Arrow(direction)), _) | (_, Some(Glyph::Arrow(direction))) => Some(*direction),
+        _ => None
+    };
+    let digits = match (glyphs.first(), glyphs.last()) {
+        (Some(Glyph::Arrow(_)), _) => &glyphs[1..],
+        (_, Some(Glyph::Arrow(_))) => &glyphs[..glyphs.len() - 1],
+        _ => &glyphs[..]
+    };
+
+    let strength = parse_number(digits, "wind")?;
+    if strength > 100 || (strength > 0 && direction.is_none()) {
+        return Err(HudError::Malformed("wind"))
+    }
+
+    Ok(Wind { strength: strength as u8, direction })
+}
+
+/// Read a number, which may be negative, from `region`, failing if it isn't in `range`.
+fn read_number(bitmap: &Bitmap<ARGB>, glyphs: &GlyphSet, region: Rect, range: RangeInclusive<i32>, field: &'static str) -> Result<i32, HudError> {
+    let glyphs = read_glyphs(bitmap, glyphs, region, field)?;
+    let number = parse_signed(&glyphs, field)?;
+
+    if !range.contains(&number) {
+        return Err(HudError::Malformed(field))
+    }
+
+    Ok(number)
+}
+
+/// Read the power readout from the menu bar.
+pub fn read_power(bitmap: &Bitmap<ARGB>, glyphs: &GlyphSet, viewport: &Viewport) -> Result<u8, HudError> {
+    let range = *POWER_RANGE.start() as i32..=*POWER_RANGE.end() as i32;
+    read_number(bitmap, glyphs, POWER_REGION.to_rect(viewport), range, "power").map(|power| power as u8)
+}
+
+/// Read the angle readout from the menu bar, which is negative when aiming below level.
+pub fn read_angle(bitmap: &Bitmap<ARGB>, glyphs: &GlyphSet, viewport: &Viewport) -> Result<i8, HudError> {
+    let range = *ANGLE_RANGE.start() as i32..=*ANGLE_RANGE.end() as i32;
+    read_number(bitmap, glyphs, ANGLE_REGION.to_rect(viewport), range, "angle").map(|angle| angle as i8)
+}
+
+/// Every value read from the HUD in a frame, each of which may have failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HudReading {
+    pub wind: Result<Wind, HudError>,
+    pub power: Result<u8, HudError>,
+    pub angle: Result<i8, HudError>
+}
+
+impl HudReading {
+    pub fn read(bitmap: &Bitmap<ARGB>, glyphs: &GlyphSet, viewport: &Viewport) -> Self {
+        Self {
+            wind: read_wind(bitmap, glyphs, viewport),
+            power: read_power(bitmap, glyphs, viewport),
+            angle: read_angle(bitmap, glyphs, viewport)
+        }
+    }
+
+    /// Copy every value that was read onto `tank`, leaving the rest unchanged.
+    /// Values typed by the user should be applied afterwards, so they take priority, see `FrameOptions`.
+    pub fn apply(&self, tank: &mut Tank) {
+        if let Ok(wind) = self.wind {
+            tank.wind = wind.relative_to(tank.direction);
+        }
+        if let Ok(power) = self.power {
+            tank.power = power;
+        }
+        if let Ok(angle) = self.angle {
+            tank.angle = angle;
+        }
+    }
+
+    /// The reasons any values couldn't be read.
+    pub fn errors(&self) -> Vec<HudError> {
+        [self.wind.err(), self.power.err(), self.angle.err()].into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::{draw_text, TextEffect, TextStyle};
+    use crate::viewport::Layout;
+
+    const BACKGROUND: ARGB = ARGB { r: 30, g: 60, b: 90, a: 255 };
+    const TEXT: ARGB = ARGB { r: 250, g: 250, b: 245, a: 255 };
+
+    // These check segmentation and matching against text drawn with the overlay's font.
+    // How well the game's own HUD is read is checked against captures of it, in `tests/captures.rs`.
+
+    fn viewport() -> Viewport {
+        Viewport::from_dimensions(Size(1280, 720), Layout::default())
+    }
+
+    fn render(pixels: &mut [ARGB], width: usize, text: &str, bottom_left: Coordinate<i32>, scale: u32) {
+        let style = TextStyle { colour: TEXT, effect: TextEffect::None, scale };
+        draw_text(&mut Bitmap::new(pixels, width), text, bottom_left, style);
+    }
+
+    fn frame(wind: &str, scale: u32) -> Vec<ARGB> {
+        let mut pixels = vec![BACKGROUND; 1280*720];
+        render(&mut pixels, 1280, wind, Coordinate(600, 670), scale);
+        pixels
+    }
+
+    #[test]
+    fn read_wind_test() {
+        for scale in [2, 3] {
+            let mut pixels = frame("17>", scale);
+            let wind = read_wind(&Bitmap::new(&mut pixels, 1280), &GlyphSet::default(), &viewport()).unwrap();
+
+            assert_eq!(wind, Wind { strength: 17, direction: Some(Direction::Right) });
+            assert_eq!(wind.relative_to(Direction::Right), 17);
+            assert_eq!(wind.relative_to(Direction::Left), -17);
+        }
+
+        let mut pixels = frame("<40", 2);
+        assert_eq!(read_wind(&Bitmap::new(&mut pixels, 1280), &GlyphSet::default(), &viewport()), Ok(Wind { strength: 40, direction: Some(Direction::Left) }));
+
+        let mut pixels = frame("0", 2);
+        assert_eq!(read_wind(&Bitmap::new(&mut pixels, 1280), &GlyphSet::default(), &viewport()), Ok(Wind { strength: 0, direction: None }));
+    }
+
+    #[test]
+    fn read_angle_test() {
+        for (text, angle) in [("-77", Ok(-77)), ("90", Ok(90)), ("-3", Ok(-3)), ("-95", Err(HudError::Malformed("angle"))), ("7-", Err(HudError::Malformed("angle")))] {
+            let mut pixels = vec![BACKGROUND; 1280*720];
+            render(&mut pixels, 1280, text, Coordinate(760, 40), 3);
+            assert_eq!(read_angle(&Bitmap::new(&mut pixels, 1280), &GlyphSet::default(), &viewport()), angle, "{text}");
+        }
+    }
+
+    #[test]
+    fn glyph_sheet_test() {
+        // a sheet at one size is used to read text at another
+        let mut sheet = vec![BACKGROUND; 300*40];
+        render(&mut sheet, 300, SHEET_CHARACTERS, Coordinate(10, 10), 4);
+        let region = Rect { origin: Coordinate(0, 0), size: Size(300, 40) };
+        let glyphs = GlyphSet::from_sheet(&Bitmap::new(&mut sheet, 300), region).unwrap();
+
+        let mut pixels = frame("<92", 3);
+        assert_eq!(read_wind(&Bitmap::new(&mut pixels, 1280), &glyphs, &viewport()), Ok(Wind { strength: 92, direction: Some(Direction::Left) }));
+
+        let mut sheet = vec![BACKGROUND; 300*40];
+        render(&mut sheet, 300, "0123", Coordinate(10, 10), 4);
+        assert_eq!(
+            GlyphSet::from_sheet(&Bitmap::new(&mut sheet, 300), region),
+            Err(HudError::GlyphSheet { found: 4, expected: SHEET_CHARACTERS.len() })
+        );
+    }
+
+    #[test]
+    fn unreadable_wind_test() {
+        let glyphs = GlyphSet::default();
+        let mut pixels = frame("", 2);
+        assert_eq!(read_wind(&Bitmap::new(&mut pixels, 1280), &glyphs, &viewport()), Err(HudError::Missing("wind")));
+
+        // a smudged character is reported, rather than read as the closest digit
+        let mut pixels = frame("8<", 3);
+        for y in 680..690 {
+            pixels[y*1280 + 600..y*1280 + 615].fill(TEXT);
+        }
+        assert!(matches!(read_wind(&Bitmap::new(&mut pixels, 1280), &glyphs, &viewport()), Err(HudError::Unrecognised { field: "wind", .. })));
+
+        // a strength without an arrow
+        let mut pixels = frame("5", 2);
+        assert_eq!(read_wind(&Bitmap::new(&mut pixels, 1280), &glyphs, &viewport()), Err(HudError::Malformed("wind")));
+    }
+
+    #[test]
+    fn hud_reading_test() {
+        let mut pixels = frame("9<", 2);
+        render(&mut pixels, 1280, "75", Coordinate(440, 40), 3);
+        // the angle is out of range, so isn't used
+        render(&mut pixels, 1280, "-95", Coordinate(760, 40), 3);
+
+        let reading = HudReading::read(&Bitmap::new(&mut pixels, 1280), &GlyphSet::default(), &viewport());
+        assert_eq!(reading.power, Ok(75));
+        assert_eq!(reading.angle, Err(HudError::Malformed("angle")));
+        assert_eq!(reading.errors(), [HudError::Malformed("angle")]);
+
+        let mut tank = Tank::new(10, 30, 0, Direction::Right);
+        reading.apply(&mut tank);
+        assert_eq!((tank.power, tank.angle, tank.wind), (75, 30, -9));
+    }
+}
@@ -1,65 +1,501 @@
-use std::cmp;
+use std::cell::OnceCell;
+
+use thiserror::Error;
 
 use crate::bitmap::{Bitmap, ARGB};
-use crate::{Coordinate, Size};
+use crate::tank::Direction;
+use crate::viewport::{Layout, Viewport};
+use crate::{Coordinate, Rect, Size};
 
+// Fractions of the game area, see `Viewport`. These are the defaults of `Layout`, which can be changed in the settings file
 pub const TANK_HEIGHT_FRACTION: f32 = 0.019535;
 pub const TANK_WIDTH_FRACTION: f32 = 0.01736;
 pub const MENU_BAR: f32 = 0.17037037;
-pub const OVERLAP_PIXELS: usize = 50;
+/// Matches with a lower confidence than this are rejected as noise.
+pub const MIN_CONFIDENCE: f32 = 0.1;
+/// `estimate_direction` keeps the previous direction if its confidence is below this.
+pub const MIN_DIRECTION_CONFIDENCE: f32 = 0.3;
+/// `find_tank` rejects its match as ambiguous if the runner up has at least this fraction of its confidence.
+pub const AMBIGUITY_RATIO: f32 = 0.9;
+/// The most candidates reported for each team by `find_tanks`.
+pub const MAX_CANDIDATES_PER_TEAM: usize = 8;
+
+pub fn tank_size_for_dimensions(dimensions: Size<usize>, layout: Layout) -> Size<usize> {
+    Viewport::new(dimensions, layout).tank_size()
+}
+
+/// The colour classes of tanks that can be detected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Team {
+    /// The player's own tank, which is green
+    Player,
+    /// Tanks on the opposing team, which are red
+    Enemy
+}
+
+impl Team {
+    pub const ALL: [Team; 2] = [Team::Player, Team::Enemy];
+
+    /// How strongly a pixel matches the team's colour, from 0 to 255.
+    pub fn pixel_score(self, pixel: ARGB) -> f32 {
+        match self {
+            Team::Player => pixel.g.saturating_sub(pixel.r).saturating_sub(pixel.b) as f32,
+            Team::Enemy => pixel.r.saturating_sub(pixel.g).saturating_sub(pixel.b) as f32
+        }
+    }
+}
+
+/// The fraction of the highest possible score a window has, where every pixel scores 255.
+pub fn window_confidence(score: f32, window_size: Size<usize>) -> f32 {
+    let max_score = (window_size.0 * window_size.1) as f32 * 255.0;
+    if max_score > 0.0 { (score / max_score).clamp(0.0, 1.0) } else { 0.0 }
+}
+
+/// The player's tank, found by `find_tank`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detection {
+    /// The centre of the tank, relative to bottom left
+    pub position: Coordinate<u32>,
+    /// The window the tank was found in
+    pub window: Rect,
+    /// The summed pixel score of the tank's window
+    pub score: f32,
+    /// See `window_confidence`
+    pub confidence: f32
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum DetectionError {
+    #[error("The search region is smaller than a tank")]
+    EmptyRegion,
+    #[error("Tank not found (confidence {confidence:.2} is below {threshold:.2})")]
+    BelowThreshold { confidence: f32, threshold: f32 },
+    #[error("Tank position is ambiguous (best confidence {best:.2}, runner up {runner_up:.2})")]
+    AmbiguousPeaks { best: f32, runner_up: f32 }
+}
+
+/// The way a tank is facing, estimated from its sprite.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionEstimate {
+    pub direction: Direction,
+    /// From 0, where both sides look the same, to 1, where everything is on one side
+    pub confidence: f32
+}
+
+/// A tank found by `find_tanks`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TankCandidate {
+    /// The centre of the tank, relative to bottom left
+    pub position: Coordinate<u32>,
+    /// The window the tank was found in
+    pub window: Rect,
+    pub team: Team,
+    /// The summed pixel score of the tank's window
+    pub score: f32,
+    /// See `window_confidence`
+    pub confidence: f32
+}
+
+/// A summed-area table (integral image), allowing the sum of any rectangle of a bitmap to be found in O(1).
+pub struct SummedAreaTable {
+    /// `sums[y*(width+1) + x]` is the sum of every value below and to the left of (x, y), exclusive.
+    /// The first row and column are zero.
+    sums: Vec<f64>,
+    width: usize
+}
+
+impl SummedAreaTable {
+    pub fn new(bitmap: &Bitmap<f32>) -> Self {
+        Self::from_fn(bitmap.width, bitmap.height(), |i| bitmap.inner[i])
+    }
+
+    /// The table of `team`'s score for every pixel of `bitmap`, see `Team::pixel_score`.
+    pub fn for_team(bitmap: &Bitmap<ARGB>, team: Team) -> Self {
+        Self::from_fn(bitmap.width, bitmap.height(), |i| team.pixel_score(bitmap.inner[i]))
+    }
+
+    /// Builds the table of a `width` by `height` bitmap, where `value` gives the value at each index.
+    fn from_fn(width: usize, height: usize, value: impl Fn(usize) -> f32) -> Self {
+        let stride = width + 1;
+        let mut sums = vec![0.0; stride * (height + 1)];
+
+        for y in 0..height {
+            let mut row_sum = 0.0;
+
+            for x in 0..width {
+                row_sum += value(y*width + x) as f64;
+                sums[(y+1)*stride + x + 1] = sums[y*stride + x + 1] + row_sum;
+            }
+        }
+
+        Self { sums, width }
+    }
+
+    /// The sum of the `size` window with its bottom-left corner at `from`.
+    pub fn window_sum(&self, from: Coordinate<usize>, size: Size<usize>) -> f64 {
+        let stride = self.width + 1;
+        let (left, right) = (from.0, from.0 + size.0);
+        let (bottom, top) = (from.1, from.1 + size.1);
+
+        self.sums[top*stride + right] - self.sums[bottom*stride + right]
+            - self.sums[top*stride + left] + self.sums[bottom*stride + left]
+    }
+
+    /// Finds up to `max_peaks` of the highest scoring `window_size` windows inside `region`, best first.
+    ///
+    /// Windows within two window sizes of a stronger peak are suppressed, along with every window that can be
+    /// reached from the peak without the score rising or reaching zero, so a blob of any size is only reported once.
+    pub fn peaks(&self, region: Rect, window_size: Size<usize>, max_peaks: usize) -> Vec<(Rect, f32)> {
+        if window_size.0 == 0 || window_size.1 == 0 {
+            return Vec::new()
+        }
+        let (Some(cols), Some(rows)) = (
+            (region.size.0 + 1).checked_sub(window_size.0),
+            (region.size.1 + 1).checked_sub(window_size.1)
+        ) else {
+            return Vec::new()
+        };
+        let from = region.origin;
+
+        let sums: Vec<f64> = (0..rows*cols)
+            .map(|i| self.window_sum(Coordinate(from.0 + i % cols, from.1 + i / cols), window_size))
+            .collect();
+        let mut suppressed = vec![false; sums.len()];
+
+        let mut peaks = Vec::new();
+        let radius = Size(2*window_size.0, 2*window_size.1);
+        let mut to_visit = Vec::new();
+
+        while peaks.len() < max_peaks {
+            // reversed so ties go to the first window, as max_by returns the last maximum
+            let Some((best, score)) = sums.iter().copied().enumerate().rev()
+                .filter(|(i, _)| !suppressed[*i])
+                .max_by(|a, b| a.1.total_cmp(&b.1)) else { break };
+
+            let (col, row) = (best % cols, best / cols);
+            peaks.push((Rect { origin: Coordinate(from.0 + col, from.1 + row), size: window_size }, score as f32));
+
+            // flood the rest of the blob, which a plateau wider than the suppression radius would otherwise be reported from again
+            suppressed[best] = true;
+            to_visit.push(best);
+            while let Some(current) = to_visit.pop() {
+                let (col, row) = (current % cols, current / cols);
+                let neighbours = [
+                    (col > 0).then(|| current - 1),
+                    (col + 1 < cols).then_some(current + 1),
+                    (row > 0).then(|| current - cols),
+                    (row + 1 < rows).then_some(current + cols)
+                ];
+
+                for neighbour in neighbours.into_iter().flatten() {
+                    if !suppressed[neighbour] && sums[neighbour] > 0.0 && sums[neighbour] <= sums[current] {
+                        suppressed[neighbour] = true;
+                        to_visit.push(neighbour);
+                    }
+                }
+            }
+
+            for suppress_row in row.saturating_sub(radius.1)..(row + radius.1 + 1).min(rows) {
+                let start = suppress_row*cols;
+                suppressed[start + col.saturating_sub(radius.0)..start + (col + radius.0 + 1).min(cols)].fill(true);
+            }
+        }
+
+        peaks
+    }
+}
+
+/// The pixel scores of one frame, shared by every search in it.
+///
+/// Each team's `SummedAreaTable` is built the first time it is needed, then reused,
+/// so a frame is scored at most once per team.
+pub struct FrameScores<'a> {
+    bitmap: &'a Bitmap<'a, ARGB>,
+    tables: [OnceCell<SummedAreaTable>; 2]
+}
+
+impl<'a> FrameScores<'a> {
+    pub fn new(bitmap: &'a Bitmap<'a, ARGB>) -> Self {
+        Self { bitmap, tables: [OnceCell::new(), OnceCell::new()] }
+    }
+
+    pub fn dimensions(&self) -> Size<usize> {
+        Size(self.bitmap.width, self.bitmap.height())
+    }
+
+    pub fn table(&self, team: Team) -> &SummedAreaTable {
+        let index = match team {
+            Team::Player => 0,
+            Team::Enemy => 1
+        };
+        self.tables[index].get_or_init(|| SummedAreaTable::for_team(self.bitmap, team))
+    }
+}
+
+/// The area tanks are searched for in, the game area above the menu bar.
+pub fn search_region(dimensions: Size<usize>, layout: Layout) -> Rect {
+    Viewport::new(dimensions, layout).play_area()
+}
 
-fn tank_size_for_dimensions(dimensions: Size<usize>) -> Size<usize> {
-    let width = dimensions.0 as f32 * TANK_WIDTH_FRACTION;
-    let height = dimensions.1 as f32 * TANK_HEIGHT_FRACTION;
+/// Finds every tank on screen, of every team, sorted by descending confidence.
+///
+/// Windows near a stronger candidate of the same team are suppressed,
+/// and candidates below `MIN_CONFIDENCE` are discarded.
+pub fn find_tanks(scores: &FrameScores, layout: Layout) -> Vec<TankCandidate> {
+    let dimensions = scores.dimensions();
+    let tank_size = tank_size_for_dimensions(dimensions, layout);
 
-    Size(width as usize, height as usize)
+    let mut candidates = Vec::new();
+
+    for team in Team::ALL {
+        for (window, score) in scores.table(team).peaks(search_region(dimensions, layout), tank_size, MAX_CANDIDATES_PER_TEAM) {
+            let confidence = window_confidence(score, tank_size);
+            if confidence < MIN_CONFIDENCE {
+                break
+            }
+
+            candidates.push(TankCandidate { position: window.centre(), window, team, score, confidence });
+        }
+    }
+
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    candidates
 }
 
-fn pixel_score(pixel: ARGB) -> f32 {
-    pixel.g.saturating_sub(pixel.r).saturating_sub(pixel.b) as f32
+/// Finds the player's tank. Coordinates are relative to bottom left.
+///
+/// Fails if the best match is too weak to be distinguished from noise,
+/// or if another match is almost as strong.
+pub fn find_tank(scores: &FrameScores, layout: Layout) -> Result<Detection, DetectionError> {
+    let region = search_region(scores.dimensions(), layout);
+    find_tank_in_region(scores, region, layout)
 }
 
-fn rolling_sum_bitmap(bitmap: &Bitmap<f32>, from: Coordinate<usize>, to: Coordinate<usize>, window_size: Size<usize>, overlap: usize) -> Option<Coordinate<usize>> {
-    // from is a coordinate that must be less than to
-    let game_dimensions = (to.0 - from.0, to.1 - from.1);
-    let mut highest_rect = (f32::MIN, None);
+/// Like `find_tank`, but only searches windows that lie entirely inside `region`.
+///
+/// The region is searched in the table `scores` already holds, so falling back to `find_tank`
+/// on a miss doesn't score the frame again.
+pub fn find_tank_in_region(scores: &FrameScores, region: Rect, layout: Layout) -> Result<Detection, DetectionError> {
+    let dimensions = scores.dimensions();
+    let tank_size = tank_size_for_dimensions(dimensions, layout);
+    let region = region.intersection(&search_region(dimensions, layout)).ok_or(DetectionError::EmptyRegion)?;
+
+    let peaks = scores.table(Team::Player).peaks(region, tank_size, 2);
+    let (window, score) = *peaks.first().ok_or(DetectionError::EmptyRegion)?;
+
+    let confidence = window_confidence(score, tank_size);
+    if confidence < MIN_CONFIDENCE {
+        return Err(DetectionError::BelowThreshold { confidence, threshold: MIN_CONFIDENCE })
+    }
+
+    if let Some((_, runner_up_score)) = peaks.get(1) {
+        let runner_up = window_confidence(*runner_up_score, tank_size);
+        if runner_up >= confidence * AMBIGUITY_RATIO {
+            return Err(DetectionError::AmbiguousPeaks { best: confidence, runner_up })
+        }
+    }
 
-    for col_count in 0..((game_dimensions.0 - window_size.0) / overlap) {
-        let pixel_col = col_count*overlap + from.0;
+    Ok(Detection { position: window.centre(), window, score, confidence })
+}
+
+/// Estimate which way the player's tank at `position` is facing from its barrel.
+///
+/// The barrel sticks out above the body towards the way the tank is facing, so the player coloured pixels
+/// above the body on either side of its centre are compared. Returns `None` if nothing is above the body.
+pub fn estimate_direction(bitmap: &Bitmap<ARGB>, position: Coordinate<u32>, layout: Layout) -> Option<DirectionEstimate> {
+    let (width, height) = (bitmap.width, bitmap.height());
+    let tank_size = tank_size_for_dimensions(Size(width, height), layout);
+    let centre = Coordinate(position.0 as usize, position.1 as usize);
 
-        for row_count in 0..((game_dimensions.1 - window_size.1) / overlap) {
-            let pixel_row = row_count*overlap + from.1;
-            
-            let rows = bitmap.subrect(Coordinate(pixel_col, pixel_row), Size(window_size.0, window_size.1));
-            let score = rows.fold(0.0, |acc, row| acc + row.iter().sum::<f32>());
+    let bottom = (centre.1 + tank_size.1/2 + 1).min(height);
+    let top = (centre.1 + tank_size.1 + tank_size.1/2 + 1).min(height);
+    let left = centre.0.saturating_sub(tank_size.0);
+    let right = (centre.0 + tank_size.0 + 1).min(width);
 
-            if score > highest_rect.0 {
-                highest_rect = (score, Some(Coordinate(pixel_col, pixel_row)))
+    let (mut left_sum, mut right_sum) = (0.0, 0.0);
+    for y in bottom..top {
+        for x in left..right {
+            let score = Team::Player.pixel_score(bitmap.inner[y*width + x]);
+            if x < centre.0 {
+                left_sum += score;
+            } else if x > centre.0 {
+                right_sum += score;
             }
         }
     }
 
-    highest_rect.1
+    let total = left_sum + right_sum;
+    if total <= 0.0 {
+        return None
+    }
+
+    let direction = if right_sum >= left_sum { Direction::Right } else { Direction::Left };
+    Some(DirectionEstimate { direction, confidence: (right_sum - left_sum).abs() / total })
 }
 
-// relative to bottom left
-pub fn find_tank(bitmap: &Bitmap<ARGB>, score_bitmap: &mut Bitmap<f32>) -> Option<Coordinate<u32>> {
-    let dimensions = Size(bitmap.width, bitmap.height());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_scores(width: usize, height: usize) -> Vec<f32> {
+        (0..width*height).map(|i| ((i * 7919) % 101) as f32 - 30.0).collect()
+    }
+
+    #[test]
+    fn window_sum_test() {
+        let mut scores = test_scores(13, 9);
+        let bitmap = Bitmap::new(&mut scores, 13);
+        let table = SummedAreaTable::new(&bitmap);
+
+        for (from, size) in [(Coordinate(0, 0), Size(13, 9)), (Coordinate(2, 3), Size(4, 5)), (Coordinate(12, 8), Size(1, 1))] {
+            let expected: f32 = bitmap.subrect(from, size).flatten().sum();
+            assert!((table.window_sum(from, size) as f32 - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn peak_matches_brute_force_test() {
+        let mut scores = test_scores(40, 30);
+        let bitmap = Bitmap::new(&mut scores, 40);
+        let table = SummedAreaTable::new(&bitmap);
+
+        let (region, window) = (Rect { origin: Coordinate(3, 5), size: Size(34, 25) }, Size(6, 4));
+
+        // sum every window directly, ties going to the first window like `peaks`
+        let (expected_origin, expected_score) = (region.origin.1..=region.origin.1 + region.size.1 - window.1)
+            .flat_map(|y| (region.origin.0..=region.origin.0 + region.size.0 - window.0).map(move |x| Coordinate(x, y)))
+            .map(|origin| (origin, bitmap.subrect(origin, window).flatten().sum::<f32>()))
+            .fold((Coordinate(0, 0), f32::MIN), |best, window| if window.1 > best.1 { window } else { best });
+
+        let (peak, score) = table.peaks(region, window, 1)[0];
+
+        assert_eq!(peak.origin, expected_origin);
+        assert!((score - expected_score).abs() < 1e-3);
+    }
+
+    #[test]
+    fn find_tanks_test() {
+        let (width, height) = (640, 360);
+        let green = ARGB { r: 10, g: 230, b: 10, a: 255 };
+        let red = ARGB { r: 230, g: 20, b: 10, a: 255 };
+        let dull_red = ARGB { r: 120, g: 40, b: 30, a: 255 };
+
+        // 11x7 pixel tanks at this size
+        let tanks = [(Coordinate(100, 200), green), (Coordinate(400, 150), red), (Coordinate(500, 300), dull_red)];
+
+        let mut pixels = vec![ARGB { r: 40, g: 50, b: 60, a: 255 }; width*height];
+        for (corner, colour) in tanks {
+            for y in corner.1..corner.1+7 {
+                pixels[y*width + corner.0..y*width + corner.0 + 11].fill(colour);
+            }
+        }
+
+        let candidates = find_tanks(&FrameScores::new(&Bitmap::new(&mut pixels, width)), Layout::default());
+
+        assert_eq!(candidates.len(), 3);
+        assert_eq!((candidates[0].team, candidates[0].position), (Team::Player, Coordinate(105, 203)));
+        assert_eq!((candidates[1].team, candidates[1].position), (Team::Enemy, Coordinate(405, 153)));
+        assert_eq!((candidates[2].team, candidates[2].position), (Team::Enemy, Coordinate(505, 303)));
+        assert!(candidates[2].confidence < candidates[1].confidence);
+    }
+
+    fn scene(width: usize, height: usize, tanks: &[(Coordinate<usize>, ARGB)]) -> Vec<ARGB> {
+        let mut pixels = vec![ARGB { r: 40, g: 50, b: 60, a: 255 }; width*height];
+        for (corner, colour) in tanks {
+            for y in corner.1..corner.1+7 {
+                pixels[y*width + corner.0..y*width + corner.0 + 11].fill(*colour);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn custom_layout_test() {
+        let green = ARGB { r: 10, g: 230, b: 10, a: 255 };
+        let mut pixels = scene(640, 360, &[(Coordinate(100, 100), green)]);
+        // a taller menu bar, and tanks twice the usual size
+        let layout = Layout { tank_width: 2.0*TANK_WIDTH_FRACTION, tank_height: 2.0*TANK_HEIGHT_FRACTION, menu_bar: 0.5 };
 
-    let tank_size = tank_size_for_dimensions(dimensions);
-    let menu_size_pixels = (dimensions.1 as f32 * MENU_BAR) as usize;
- 
-    for (i, pixel) in score_bitmap.inner.iter_mut().enumerate() {
-        *pixel = pixel_score(bitmap.inner[i])
+        assert_eq!(search_region(Size(640, 360), layout), Rect { origin: Coordinate(0, 180), size: Size(640, 180) });
+        assert_eq!(tank_size_for_dimensions(Size(640, 360), layout), Size(22, 14));
+
+        // the tank is found with the default layout, but is on the menu bar of the custom one
+        assert!(find_tank(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default()).is_ok());
+        let result = find_tank(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), layout);
+        assert!(result.is_err(), "{result:?}");
     }
 
-    let most_likely_rect = rolling_sum_bitmap(&score_bitmap, Coordinate(0, menu_size_pixels), Coordinate(dimensions.0, dimensions.1), tank_size, OVERLAP_PIXELS)?;
-    let expanded_from = Coordinate(most_likely_rect.0.saturating_sub(tank_size.0), most_likely_rect.1.saturating_sub(tank_size.1));
-    let expanded_to = Coordinate(
-        cmp::min(dimensions.0, expanded_from.0 + 3*tank_size.0),
-        cmp::min(dimensions.1, expanded_from.1 + 3*tank_size.1)
-    );
-    
-    let closer_rect = rolling_sum_bitmap(&score_bitmap, expanded_from, expanded_to, tank_size, 1)?;
-    Some(Coordinate((closer_rect.0 + tank_size.0/2) as u32, (closer_rect.1 + tank_size.1/2) as u32))
+    #[test]
+    fn find_tank_errors_test() {
+        let green = ARGB { r: 10, g: 230, b: 10, a: 255 };
+
+        let mut pixels = scene(640, 360, &[(Coordinate(100, 200), green)]);
+        let detection = find_tank(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default()).unwrap();
+        assert_eq!(detection.position, Coordinate(105, 203));
+        assert_eq!(detection.window, Rect { origin: Coordinate(100, 200), size: Size(11, 7) });
+        assert!(detection.confidence > 0.8);
+
+        let mut pixels = scene(640, 360, &[]);
+        let result = find_tank(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default());
+        assert!(matches!(result, Err(DetectionError::BelowThreshold { .. })));
+
+        let mut pixels = scene(640, 360, &[(Coordinate(100, 200), green), (Coordinate(400, 200), green)]);
+        let result = find_tank(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default());
+        assert!(matches!(result, Err(DetectionError::AmbiguousPeaks { .. })));
+
+        let mut pixels = scene(8, 8, &[]);
+        let result = find_tank(&FrameScores::new(&Bitmap::new(&mut pixels, 8)), Layout::default());
+        assert_eq!(result, Err(DetectionError::EmptyRegion));
+    }
+
+    #[test]
+    fn large_blob_test() {
+        // a blob much larger than a tank, which used to be reported again from its far side
+        let mut pixels = scene(640, 360, &[]);
+        for y in 200..240 {
+            pixels[y*640 + 300..y*640 + 340].fill(ARGB { r: 10, g: 230, b: 10, a: 255 });
+        }
+
+        let bitmap = Bitmap::new(&mut pixels, 640);
+        let scores = FrameScores::new(&bitmap);
+        let detection = find_tank(&scores, Layout::default()).unwrap();
+        assert_eq!(detection.window.origin, Coordinate(300, 200));
+
+        let peaks = scores.table(Team::Player).peaks(search_region(Size(640, 360), Layout::default()), Size(11, 7), 4);
+        assert!(peaks.iter().skip(1).all(|(_, score)| *score == 0.0), "{peaks:?}");
+    }
+
+    #[test]
+    fn window_larger_than_region_test() {
+        let mut scores = test_scores(10, 10);
+        let bitmap = Bitmap::new(&mut scores, 10);
+
+        let region = Rect { origin: Coordinate(0, 0), size: Size(10, 10) };
+        assert!(SummedAreaTable::new(&bitmap).peaks(region, Size(11, 2), 1).is_empty());
+    }
+
+    #[test]
+    fn estimate_direction_test() {
+        let green = ARGB { r: 10, g: 230, b: 10, a: 255 };
+
+        for direction in [Direction::Left, Direction::Right] {
+            let mut pixels = scene(640, 360, &[(Coordinate(100, 200), green)]);
+            // a barrel pointing up and away from the turret
+            for step in 0..6 {
+                let x = match direction {
+                    Direction::Left => 104 - step,
+                    Direction::Right => 106 + step
+                };
+                pixels[(207 + step)*640 + x] = green;
+            }
+            let mut bitmap = Bitmap::new(&mut pixels, 640);
+
+            let estimate = estimate_direction(&bitmap, Coordinate(105, 203), Layout::default()).unwrap();
+            assert_eq!(estimate.direction, direction);
+            assert!(estimate.confidence > 0.9);
+
+            bitmap.fill(ARGB { r: 40, g: 50, b: 60, a: 255 });
+            assert_eq!(estimate_direction(&bitmap, Coordinate(105, 203), Layout::default()), None);
+        }
+    }
 }
@@ -0,0 +1,87 @@
+use crate::{Coordinate, Rect};
+use crate::image_processing::TankCandidate;
+use crate::tank::Tank;
+use crate::trajectory::Trajectory;
+use crate::viewport::Viewport;
+
+/// Where a projectile lands, and what it would hit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImpactMarker {
+    /// Relative to bottom left
+    pub position: Coordinate<i32>,
+    /// In pixels. `None` where the shot leaves the game area rather than exploding
+    pub blast_radius: Option<f32>,
+    /// The windows of the tanks inside the blast
+    pub hits: Vec<Rect>
+}
+
+/// Whether any of `window` is within `radius` pixels of `point`.
+fn in_blast(point: Coordinate<i32>, radius: f32, window: Rect) -> bool {
+    let distance = |point: i32, start: usize, end: usize| (start as i32 - point).max(point - (end as i32 - 1)).max(0) as f32;
+    let dx = distance(point.0, window.origin.0, window.end().0);
+    let dy = distance(point.1, window.origin.1, window.end().1);
+
+    dx.hypot(dy) <= radius
+}
+
+/// A marker for each projectile of `tank`'s weapon that explodes, with the blast radius of the weapon and the `tanks` inside it.
+///
+/// If none explode, a single marker without a blast where the shot leaves the game area, see `Trajectory::landing`.
+pub fn impact_markers(tank: &Tank, trajectory: &Trajectory, viewport: &Viewport, tanks: &[TankCandidate]) -> Vec<ImpactMarker> {
+    let impacts = trajectory.impacts();
+    if impacts.is_empty() {
+        return trajectory.landing().map(|position| ImpactMarker { position, blast_radius: None, hits: Vec::new() }).into_iter().collect()
+    }
+
+    let radius = tank.blast_radius(viewport);
+    impacts.into_iter().map(|position| ImpactMarker {
+        position,
+        blast_radius: Some(radius),
+        hits: tanks.iter().filter(|candidate| in_blast(position, radius, candidate.window)).map(|candidate| candidate.window).collect()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Size;
+    use crate::image_processing::Team;
+    use crate::tank::Direction;
+    use crate::viewport::Layout;
+    use crate::weapon::{ProjectilePath, Weapon};
+
+    fn viewport(dimensions: Size<u32>) -> Viewport {
+        Viewport::from_dimensions(dimensions, Layout::default())
+    }
+
+    fn candidate(origin: Coordinate<usize>) -> TankCandidate {
+        let window = Rect { origin, size: Size(20, 10) };
+        TankCandidate { position: window.centre(), window, team: Team::Enemy, score: 1.0, confidence: 1.0 }
+    }
+
+    #[test]
+    fn impact_markers_test() {
+        let tank = Tank { weapon: Weapon::Heavy, ..Tank::new(50, 45, 0, Direction::Right) };
+        let trajectory = Trajectory::from_paths(vec![ProjectilePath::through(&[(0, 0), (500, 300)], true, 10.0)]);
+        // the edge of the window is in the blast even though its centre isn't
+        let tanks = [candidate(Coordinate(560, 295)), candidate(Coordinate(575, 295)), candidate(Coordinate(490, 270))];
+
+        let markers = impact_markers(&tank, &trajectory, &viewport(Size(2560, 1440)), &tanks);
+        assert_eq!(markers, [ImpactMarker { position: Coordinate(500, 300), blast_radius: Some(70.0), hits: vec![tanks[0].window, tanks[2].window] }]);
+
+        // scaled with the game area
+        let markers = impact_markers(&tank, &trajectory, &viewport(Size(1280, 720)), &tanks);
+        assert_eq!(markers[0].blast_radius, Some(35.0));
+        assert_eq!(markers[0].hits, [tanks[2].window]);
+    }
+
+    #[test]
+    fn leaves_screen_test() {
+        let tank = Tank::new(50, 45, 0, Direction::Right);
+        let trajectory = Trajectory::from_paths(vec![ProjectilePath::through(&[(0, 0), (40, 90)], false, 3.0), ProjectilePath::through(&[(0, 0), (80, 20)], false, 5.0)]);
+
+        let markers = impact_markers(&tank, &trajectory, &viewport(Size(2560, 1440)), &[candidate(Coordinate(75, 15))]);
+        assert_eq!(markers, [ImpactMarker { position: Coordinate(80, 20), blast_radius: None, hits: Vec::new() }]);
+        assert!(impact_markers(&tank, &Trajectory::default(), &viewport(Size(2560, 1440)), &[]).is_empty());
+    }
+}
@@ -3,14 +3,61 @@
 
 pub mod event_loop;
 pub mod tank;
+pub mod tracking;
+pub mod terrain;
+pub mod hud;
+pub mod backend;
+#[cfg(windows)]
 pub mod window_winapi;
 pub mod bitmap;
 pub mod image_processing;
+pub mod codec;
+pub mod draw;
+pub mod font;
+pub mod offline;
+pub mod solver;
+pub mod calibration;
+pub mod viewport;
+pub mod weapon;
+pub mod trajectory;
+pub mod fan;
+pub mod impact;
+pub mod settings;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 /// x, y coordinate
 pub struct Coordinate<T>(pub T, pub T);
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 /// A size in 2D space
 pub struct Size<T>(pub T, pub T);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// A rectangle, positioned by its bottom-left corner
+pub struct Rect {
+    pub origin: Coordinate<usize>,
+    pub size: Size<usize>
+}
+
+impl Rect {
+    /// The coordinate one past the top-right corner
+    pub fn end(&self) -> Coordinate<usize> {
+        Coordinate(self.origin.0 + self.size.0, self.origin.1 + self.size.1)
+    }
+
+    pub fn centre(&self) -> Coordinate<u32> {
+        Coordinate((self.origin.0 + self.size.0/2) as u32, (self.origin.1 + self.size.1/2) as u32)
+    }
+
+    /// The overlapping area of two rectangles, if they overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let origin = Coordinate(self.origin.0.max(other.origin.0), self.origin.1.max(other.origin.1));
+        let end = Coordinate(self.end().0.min(other.end().0), self.end().1.min(other.end().1));
+
+        if end.0 <= origin.0 || end.1 <= origin.1 {
+            return None
+        }
+
+        Some(Rect { origin, size: Size(end.0 - origin.0, end.1 - origin.1) })
+    }
+}
@@ -1,10 +1,172 @@
+use std::env;
 use std::error::Error;
 
-use shellshock_tracer::window_winapi::{create_window, get_shellshock_window, create_dibitmap, window_dimensions, create_pen};
-use shellshock_tracer::bitmap::ARGB;
-use shellshock_tracer::event_loop::{WindowsObjects, Config, event_loop};
+use shellshock_tracer::impact::impact_markers;
+use shellshock_tracer::offline::{analyze_screenshot, render_trajectory};
+use shellshock_tracer::settings::{Settings, DEFAULT_SETTINGS};
+use shellshock_tracer::tank::{Border, Direction, Physics, Tank};
+use shellshock_tracer::viewport::Viewport;
+use shellshock_tracer::weapon::Weapon;
+
+#[cfg(windows)]
+use shellshock_tracer::window_winapi::{
+    create_window, get_shellshock_window, create_dibitmap, window_dimensions,
+    WindowsObjects, WindowsFrameSource, WindowsOverlay
+};
+#[cfg(windows)]
+use shellshock_tracer::event_loop::{Config, event_loop};
+#[cfg(windows)]
+use shellshock_tracer::tracking::TrackingConfig;
+#[cfg(windows)]
+use shellshock_tracer::{Coordinate, Rect, Size};
+#[cfg(windows)]
+use shellshock_tracer::backend::FrameSource;
+#[cfg(windows)]
+use shellshock_tracer::bitmap::BitmapBuf;
+#[cfg(windows)]
+use shellshock_tracer::calibration::{calibrate, record_shot};
+#[cfg(windows)]
+use shellshock_tracer::image_processing::{find_tank, FrameScores};
+#[cfg(windows)]
+use shellshock_tracer::hud::GlyphSet;
+
+/// How long a shot is recorded for when calibrating.
+#[cfg(windows)]
+const CALIBRATION_DURATION: std::time::Duration = std::time::Duration::from_secs(10);
+
+const USAGE: &str = "\
+Usage:
+    shellshock-tracer [--config <settings>] <command>
+                                            Run a command using a settings file, see default-config
+    shellshock-tracer [--profile <profile>] Run the live tracer overlay, using a calibration profile if given
+    shellshock-tracer calibrate <power> <angle> <wind> <direction: left/right> <profile>
+                                            Record a shot fired with these values and save the fitted physics
+    shellshock-tracer analyze <screenshot>  Run tank detection on a screenshot
+    shellshock-tracer render <screenshot> <output> <power> <angle> <wind> <direction: left/right/auto> [weapon [border]]
+                                            Draw the predicted trajectory onto a screenshot
+    shellshock-tracer default-config        Print a settings file with every setting at its default, describing each of them
+
+Images can be BMP, PNG or binary PPM files.
+Weapons are shot (the default), three-ball, five-ball, heavy, bouncer, roller, splitter and homing.
+How much the wind moves heavy shells, how bouncers bounce, rollers roll, splitters split and homing shells steer
+is approximated, so their paths are only a guide.
+Borders are stop (the default), bounce or wrap, depending on the map.
+In the live tracer, type a weapon's name to select it, or `border <border>` to change the border.
+Type `fan [power step [angle step [power steps [angle steps]]]]` to also draw the neighbouring shots, and `fan off` to stop.
+The HUD is only read when a glyph sheet is set in the settings file, otherwise the power, angle and wind must be typed.
+Typed values are used instead of those read from the HUD, until `hud` is typed.";
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let settings = if args.first().is_some_and(|arg| arg == "--config") {
+        let path = args.get(1).ok_or(USAGE)?.clone();
+        args.drain(..2);
+        Settings::load(&path).map_err(|error| format!("{path}: {error}"))?
+    } else {
+        Settings::default()
+    };
+
+    match args.first().map(String::as_str) {
+        None => run_tracer(settings),
+        Some("--profile") => run_tracer(Settings { physics: Physics::load(args.get(1).ok_or(USAGE)?)?, ..settings }),
+        Some("calibrate") => run_calibration(&args[1..], &settings),
+        Some("analyze") => analyze(&args[1..], &settings),
+        Some("render") => render(&args[1..], &settings),
+        Some("default-config") => {
+            print!("{DEFAULT_SETTINGS}");
+            Ok(())
+        },
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            Ok(())
+        },
+        Some(other) => Err(format!("Unknown command `{other}`\n{USAGE}").into())
+    }
+}
+
+fn analyze(args: &[String], settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let path = args.first().ok_or(USAGE)?;
+    let analysis = analyze_screenshot(path, settings.layout)?;
+
+    println!("Screenshot: {}x{}", analysis.dimensions.0, analysis.dimensions.1);
+    println!("Tank position (from bottom-left): {}, {}", analysis.detection.position.0, analysis.detection.position.1);
+    println!("Detection score: {} (confidence {:.2})", analysis.detection.score, analysis.detection.confidence);
+    match analysis.direction {
+        Some(estimate) => println!("Facing {:?} (confidence {:.2})", estimate.direction, estimate.confidence),
+        None => println!("Facing direction unknown")
+    }
+
+    println!("All tanks:");
+    for tank in &analysis.tanks {
+        println!("    {:?} at {}, {} (confidence {:.2})", tank.team, tank.position.0, tank.position.1, tank.confidence);
+    }
+
+    Ok(())
+}
+
+/// Parse a direction argument, where `auto` is `None`.
+fn parse_direction(direction: &str) -> Result<Option<Direction>, Box<dyn Error>> {
+    match direction {
+        "left" | "0" => Ok(Some(Direction::Left)),
+        "right" | "1" => Ok(Some(Direction::Right)),
+        "auto" => Ok(None),
+        _ => Err(format!("Invalid direction `{direction}`, expected left, right or auto").into())
+    }
+}
+
+fn render(args: &[String], settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let ([screenshot, output, power, angle, wind, direction] | [screenshot, output, power, angle, wind, direction, _] | [screenshot, output, power, angle, wind, direction, _, _]) = args else {
+        return Err(USAGE.into())
+    };
+
+    let direction_override = parse_direction(direction)?;
+    let weapon = match args.get(6) {
+        Some(name) => Weapon::from_name(name).ok_or_else(|| format!("Unknown weapon `{name}`\n{USAGE}"))?,
+        None => Weapon::default()
+    };
+    let border = match args.get(7) {
+        Some(name) => Border::from_name(name).ok_or_else(|| format!("Invalid border `{name}`, expected stop, bounce or wrap"))?,
+        None => Border::default()
+    };
+
+    let mut tank = Tank { physics: settings.physics, weapon, border, ..Tank::new(power.parse()?, angle.parse()?, wind.parse()?, Direction::Right) };
+
+    let (analysis, trajectory) = render_trajectory(screenshot, output, &mut tank, direction_override, settings.trajectory_style, settings.layout)?;
+
+    println!("Tank position (from bottom-left): {}, {}", analysis.detection.position.0, analysis.detection.position.1);
+    println!("Time of flight: {:.1}", trajectory.time_of_flight());
+    let viewport = Viewport::from_dimensions(analysis.dimensions, settings.layout);
+    let markers = impact_markers(&tank, &trajectory, &viewport, &analysis.tanks);
+    for marker in markers {
+        match marker.blast_radius {
+            Some(radius) => println!(
+                "Impact point (from bottom-left): {}, {} with a blast radius of {radius:.0}, hitting {} tank(s)",
+                marker.position.0, marker.position.1, marker.hits.len()
+            ),
+            None => println!("The shot leaves the screen at {}, {} without hitting the ground", marker.position.0, marker.position.1)
+        }
+    }
+    println!("Trajectory written to {output}");
+
+    Ok(())
+}
+
+/// The templates to read the HUD with, derived from the settings' glyph sheet if there is one.
+#[cfg(windows)]
+fn load_glyphs(settings: &Settings) -> Result<Option<GlyphSet>, Box<dyn Error>> {
+    let Some(path) = &settings.glyph_sheet else {
+        return Ok(None)
+    };
+
+    let mut sheet = BitmapBuf::load(path)?;
+    let region = Rect { origin: Coordinate(0, 0), size: Size(sheet.width(), sheet.height()) };
+    Ok(Some(GlyphSet::from_sheet(&sheet.as_bitmap(), region).map_err(|error| format!("{}: {error}", path.display()))?))
+}
+
+#[cfg(windows)]
+fn run_tracer(settings: Settings) -> Result<(), Box<dyn Error>> {
+    let glyphs = load_glyphs(&settings)?;
     let own_hwnd = create_window()?;
 
     let shellshock_hwnd = get_shellshock_window()
@@ -14,17 +176,69 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let windows_objects = WindowsObjects {
         bitmap: unsafe { create_dibitmap(own_hwnd, dimensions, 0.into())? },
-        pen: create_pen(2, ARGB { r: 200, b: 100, g: 100, a: 255 })?,
     };
-    
+
     let config = Config {
-        window_handle: own_hwnd,
-        shellshock_handle: shellshock_hwnd,
+        frame_source: WindowsFrameSource {
+            shellshock_handle: shellshock_hwnd,
+            dimensions: unsafe { window_dimensions(shellshock_hwnd)? }
+        },
+        overlay: WindowsOverlay { window_handle: own_hwnd, dimensions, windows_objects, frame: BitmapBuf::new(dimensions, 0.into()) },
         dimensions,
-        windows_objects
+        tracking: TrackingConfig::default(),
+        settings,
+        fan: None,
+        glyphs
     };
 
     event_loop(config)?;
 
     Ok(())
 }
+
+#[cfg(not(windows))]
+fn run_tracer(_settings: Settings) -> Result<(), Box<dyn Error>> {
+    Err("The live tracer overlay is only supported on Windows.".into())
+}
+
+#[cfg(windows)]
+fn run_calibration(args: &[String], settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let [power, angle, wind, direction, profile] = args else {
+        return Err(USAGE.into())
+    };
+    let direction = parse_direction(direction)?.ok_or("The direction must be given when calibrating")?;
+
+    let shellshock_hwnd = get_shellshock_window()
+        .ok_or("Shellshock application not found.")?;
+    let dimensions = unsafe { window_dimensions(shellshock_hwnd)? };
+    let mut frame_source = WindowsFrameSource { shellshock_handle: shellshock_hwnd, dimensions };
+
+    let mut screen = BitmapBuf::new(dimensions, 0.into());
+    frame_source.capture(&mut screen.as_bitmap())?;
+    let detection = find_tank(&FrameScores::new(&screen.as_bitmap()), settings.layout)?;
+
+    let tank = Tank { screen_position: detection.position, ..Tank::new(power.parse()?, angle.parse()?, wind.parse()?, direction) };
+
+    // the tank's barrel and the smoke around it move when firing, so ignore the area around it
+    let window = detection.window;
+    let ignore = Rect {
+        origin: Coordinate(window.origin.0.saturating_sub(window.size.0), window.origin.1.saturating_sub(window.size.1)),
+        size: Size(3*window.size.0, 3*window.size.1)
+    };
+
+    println!("Recording for {} seconds, fire the shot now", CALIBRATION_DURATION.as_secs());
+    let observations = record_shot(&mut frame_source, vec![ignore], settings.layout, CALIBRATION_DURATION)?;
+
+    let physics = calibrate(&tank, &Viewport::from_dimensions(dimensions, settings.layout), &observations)?;
+    physics.save(profile)?;
+
+    println!("Fitted from {} observations: wind_constant = {}, path_constant = {}", observations.len(), physics.wind_constant, physics.path_constant);
+    println!("Profile written to {profile}");
+
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn run_calibration(_args: &[String], _settings: &Settings) -> Result<(), Box<dyn Error>> {
+    Err("Calibration captures the game window, so is only supported on Windows.".into())
+}
@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::{Coordinate, Size};
+use crate::bitmap::{BitmapBuf, ARGB};
+use crate::codec::CodecError;
+use crate::draw::{draw_impact, draw_trajectory, TrajectoryStyle};
+use crate::event_loop::impact_label;
+use crate::font::{draw_text, TextStyle};
+use crate::impact::impact_markers;
+use crate::image_processing::{estimate_direction, find_tank, find_tanks, Detection, DetectionError, DirectionEstimate, FrameScores, TankCandidate, Team, MIN_DIRECTION_CONFIDENCE};
+use crate::tank::{Direction, Tank};
+use crate::terrain::Terrain;
+use crate::trajectory::Trajectory;
+use crate::viewport::{Layout, Viewport};
+
+#[derive(Error, Debug)]
+pub enum OfflineError {
+    #[error("Could not load or save image: {0}")]
+    Codec(#[from] CodecError),
+    #[error(transparent)]
+    Detection(#[from] DetectionError)
+}
+
+/// The result of running tank detection on a screenshot.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    pub dimensions: Size<u32>,
+    /// The player's tank, relative to the bottom-left of the screenshot
+    pub detection: Detection,
+    /// Every tank found, of every team
+    pub tanks: Vec<TankCandidate>,
+    pub terrain: Terrain,
+    /// The way the player's tank is facing, if it could be estimated
+    pub direction: Option<DirectionEstimate>
+}
+
+impl Analysis {
+    /// The positions of the tanks on the opposing team.
+    pub fn enemies(&self) -> Vec<Coordinate<u32>> {
+        self.tanks.iter()
+            .filter(|tank| tank.team == Team::Enemy)
+            .map(|tank| tank.position)
+            .collect()
+    }
+}
+
+fn analyze_bitmap(screenshot: &mut BitmapBuf<ARGB>, layout: Layout) -> Result<Analysis, OfflineError> {
+    let dimensions = screenshot.dimensions();
+    let bitmap = screenshot.as_bitmap();
+    let scores = FrameScores::new(&bitmap);
+
+    let detection = find_tank(&scores, layout)?;
+    let tanks = find_tanks(&scores, layout);
+    let terrain = Terrain::extract(&bitmap, layout);
+    let direction = estimate_direction(&bitmap, detection.position, layout);
+
+    Ok(Analysis { dimensions, detection, tanks, terrain, direction })
+}
+
+/// Load a screenshot from disk and run tank detection on it, with the proportions of `layout`.
+pub fn analyze_screenshot<P: AsRef<Path>>(path: P, layout: Layout) -> Result<Analysis, OfflineError> {
+    analyze_bitmap(&mut BitmapBuf::load(path)?, layout)
+}
+
+/// The direction a screenshot's tank is drawn facing, see `render_trajectory`.
+fn facing(direction_override: Option<Direction>, estimate: Option<DirectionEstimate>) -> Direction {
+    let estimated = estimate
+        .filter(|estimate| estimate.confidence >= MIN_DIRECTION_CONFIDENCE)
+        .map(|estimate| estimate.direction);
+
+    direction_override.or(estimated).unwrap_or(Direction::Right)
+}
+
+/// Load a screenshot, locate the tank and draw its predicted trajectory onto the screenshot, saving it to `output`.
+/// Where the trajectory lands is marked with the blast of the weapon, circling the tanks inside it, and labelled with the tank's power and angle.
+/// The trajectory of the tank's weapon is stopped where it hits the terrain, and homing weapons steer towards the enemy tanks found.
+///
+/// The `screen_position` of `tank` is replaced by the detected position,
+/// and its `direction` by `direction_override` or, if not given, the estimated direction.
+/// An estimate below `MIN_DIRECTION_CONFIDENCE` isn't used, leaving the tank facing right, the same as the live overlay does.
+/// Returns the drawn trajectory, drawn with `style`, along with the analysis, which uses the proportions of `layout`.
+pub fn render_trajectory<P: AsRef<Path>, Q: AsRef<Path>>(screenshot: P, output: Q, tank: &mut Tank, direction_override: Option<Direction>, style: TrajectoryStyle, layout: Layout) -> Result<(Analysis, Trajectory), OfflineError> {
+    let mut screenshot = BitmapBuf::load(screenshot)?;
+    let analysis = analyze_bitmap(&mut screenshot, layout)?;
+
+    tank.screen_position = analysis.detection.position;
+    tank.direction = facing(direction_override, analysis.direction);
+    let viewport = Viewport::from_dimensions(analysis.dimensions, layout);
+    let trajectory = tank.trajectory(&viewport, Some(&analysis.terrain), &analysis.enemies());
+    draw_trajectory(&mut screenshot.as_bitmap(), &trajectory, style);
+    for marker in impact_markers(tank, &trajectory, &viewport, &analysis.tanks) {
+        draw_impact(&mut screenshot.as_bitmap(), &marker);
+    }
+    if let Some((label, position)) = impact_label(tank, &trajectory, analysis.dimensions) {
+        draw_text(&mut screenshot.as_bitmap(), &label, position, TextStyle::default());
+    }
+
+    screenshot.save(output)?;
+
+    Ok((analysis, trajectory))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facing_test() {
+        let estimate = |confidence| Some(DirectionEstimate { direction: Direction::Left, confidence });
+
+        assert_eq!(facing(None, estimate(0.8)), Direction::Left);
+        // too close to call, as the live overlay would treat it
+        assert_eq!(facing(None, estimate(MIN_DIRECTION_CONFIDENCE / 2.0)), Direction::Right);
+        assert_eq!(facing(None, None), Direction::Right);
+        assert_eq!(facing(Some(Direction::Right), estimate(0.8)), Direction::Right);
+    }
+}
@@ -0,0 +1,260 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::bitmap::ARGB;
+use crate::draw::TrajectoryStyle;
+use crate::event_loop::LOOP_DURATION;
+use crate::tank::Physics;
+use crate::viewport::Layout;
+
+/// The settings file with every setting at its default, documenting each of them.
+/// Any setting, or whole section, can be left out to keep its default.
+pub const DEFAULT_SETTINGS: &str = "\
+# shellshock-tracer settings
+
+[overlay]
+# The colour of the trajectory, as #rrggbb or #rrggbbaa
+trajectory_colour = \"#c86464\"
+# The width of the trajectory, in pixels
+trajectory_width = 2
+# The length of the dashes of the trajectory, and the gaps between them, in pixels
+dash_length = 4.0
+
+[timing]
+# How long to wait between processing frames, in milliseconds
+frame_interval_ms = 100
+
+[detection]
+# The size of a tank, as fractions of the width and height of the game area
+tank_width_fraction = 0.01736
+tank_height_fraction = 0.019535
+# The height of the menu bar along the bottom, as a fraction of the height of the game area
+menu_bar_fraction = 0.17037037
+
+[hud]
+# A capture of the game's own HUD characters, 0123456789, the minus sign, then the left and right wind arrows, side by side.
+# The HUD is only read when this is given, otherwise the power, angle and wind must be typed, see `GlyphSet::from_sheet`
+# glyph_sheet = \"glyphs.png\"
+
+[physics]
+# On a 2560x1440 screen, replaced by a calibration profile if one is given
+wind_constant = 0.00364
+path_constant = 3.0183
+";
+
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("Could not read the settings file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid settings file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Invalid setting `{key}`: {reason}")]
+    Invalid { key: &'static str, reason: &'static str }
+}
+
+/// Everything that can be changed in the settings file, see `DEFAULT_SETTINGS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// How the current trajectory is drawn
+    pub trajectory_style: TrajectoryStyle,
+    /// How long to wait between processing frames
+    pub frame_interval: Duration,
+    /// The proportions everything is detected with, see `Viewport`
+    pub layout: Layout,
+    /// The image to derive the HUD's templates from, without which the HUD isn't read
+    pub glyph_sheet: Option<PathBuf>,
+    pub physics: Physics
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            trajectory_style: TrajectoryStyle::default(),
+            frame_interval: LOOP_DURATION,
+            layout: Layout::default(),
+            glyph_sheet: None,
+            physics: Physics::default()
+        }
+    }
+}
+
+/// The layout of the settings file. Settings left out take their value from `Settings::default`.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct SettingsFile {
+    overlay: OverlaySection,
+    timing: TimingSection,
+    detection: DetectionSection,
+    hud: HudSection,
+    physics: PhysicsSection
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct OverlaySection {
+    trajectory_colour: Option<String>,
+    trajectory_width: Option<u32>,
+    dash_length: Option<f32>
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TimingSection {
+    frame_interval_ms: Option<u64>
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DetectionSection {
+    tank_width_fraction: Option<f32>,
+    tank_height_fraction: Option<f32>,
+    menu_bar_fraction: Option<f32>
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct HudSection {
+    glyph_sheet: Option<PathBuf>
+}
+
+/// Also the layout of a calibration profile, see `calibration::parse_profile`.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PhysicsSection {
+    pub wind_constant: Option<f32>,
+    pub path_constant: Option<f32>
+}
+
+impl PhysicsSection {
+    /// Check the constants are usable, taking any left out from `default`.
+    pub fn to_physics(&self, default: Physics) -> Result<Physics, SettingsError> {
+        // a wind constant of zero is allowed, for a game mode without wind
+        let wind_constant = match self.wind_constant {
+            Some(constant) if !(constant.is_finite() && constant >= 0.0) => {
+                return Err(SettingsError::Invalid { key: "physics.wind_constant", reason: "can't be negative" })
+            },
+            constant => constant.unwrap_or(default.wind_constant)
+        };
+        let path_constant = check_between(self.path_constant, "physics.path_constant", (0.0, f32::INFINITY), "must be positive")?
+            .unwrap_or(default.path_constant);
+
+        Ok(Physics { wind_constant, path_constant })
+    }
+}
+
+/// Parse a colour written as `#rrggbb` or `#rrggbbaa`.
+fn parse_colour(colour: &str) -> Option<ARGB> {
+    let digits = colour.strip_prefix('#')?;
+    if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+        return None
+    }
+
+    let channel = |index: usize| u8::from_str_radix(digits.get(2*index..2*index + 2)?, 16).ok();
+    let a = if digits.len() == 8 { channel(3)? } else { 255 };
+    Some(ARGB { r: channel(0)?, g: channel(1)?, b: channel(2)?, a })
+}
+
+/// Check `value` is a finite number in `range`, exclusive of both ends.
+fn check_between(value: Option<f32>, key: &'static str, range: (f32, f32), reason: &'static str) -> Result<Option<f32>, SettingsError> {
+    match value {
+        Some(value) if !(value.is_finite() && value > range.0 && value < range.1) => Err(SettingsError::Invalid { key, reason }),
+        _ => Ok(value)
+    }
+}
+
+impl Settings {
+    /// Load a settings file, see `DEFAULT_SETTINGS` for the format.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SettingsError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse the contents of a settings file, checking every value is usable.
+    pub fn parse(settings: &str) -> Result<Self, SettingsError> {
+        let file: SettingsFile = toml::from_str(settings)?;
+        let default = Self::default();
+
+        let colour = match file.overlay.trajectory_colour {
+            Some(colour) => parse_colour(&colour)
+                .ok_or(SettingsError::Invalid { key: "overlay.trajectory_colour", reason: "expected #rrggbb or #rrggbbaa" })?,
+            None => default.trajectory_style.colour
+        };
+        let width = match file.overlay.trajectory_width {
+            Some(0) => return Err(SettingsError::Invalid { key: "overlay.trajectory_width", reason: "must be at least 1" }),
+            width => width.unwrap_or(default.trajectory_style.width)
+        };
+        let dash_length = check_between(file.overlay.dash_length, "overlay.dash_length", (0.0, f32::INFINITY), "must be positive")?
+            .unwrap_or(default.trajectory_style.dash_length);
+
+        let frame_interval = match file.timing.frame_interval_ms {
+            Some(0) => return Err(SettingsError::Invalid { key: "timing.frame_interval_ms", reason: "must be at least 1" }),
+            interval => interval.map_or(default.frame_interval, Duration::from_millis)
+        };
+
+        const FRACTION: &str = "must be a fraction between 0 and 1";
+        let detection = file.detection;
+        let layout = Layout {
+            tank_width: check_between(detection.tank_width_fraction, "detection.tank_width_fraction", (0.0, 1.0), FRACTION)?
+                .unwrap_or(default.layout.tank_width),
+            tank_height: check_between(detection.tank_height_fraction, "detection.tank_height_fraction", (0.0, 1.0), FRACTION)?
+                .unwrap_or(default.layout.tank_height),
+            menu_bar: check_between(detection.menu_bar_fraction, "detection.menu_bar_fraction", (0.0, 1.0), FRACTION)?
+                .unwrap_or(default.layout.menu_bar)
+        };
+
+        let physics = file.physics.to_physics(default.physics)?;
+
+        Ok(Self { trajectory_style: TrajectoryStyle { colour, width, dash_length }, frame_interval, layout, glyph_sheet: file.hud.glyph_sheet, physics })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_settings_test() {
+        assert_eq!(Settings::parse(DEFAULT_SETTINGS).unwrap(), Settings::default());
+        assert_eq!(Settings::parse("").unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn parse_settings_test() {
+        let settings = Settings::parse("
+            [overlay]
+            trajectory_colour = \"#20ff8080\"
+            dash_length = 6
+
+            [timing]
+            frame_interval_ms = 50
+
+            [hud]
+            glyph_sheet = \"captures/glyphs.png\"
+        ").unwrap();
+
+        assert_eq!(settings.trajectory_style, TrajectoryStyle { colour: ARGB { r: 0x20, g: 0xff, b: 0x80, a: 0x80 }, dash_length: 6.0, ..TrajectoryStyle::default() });
+        assert_eq!(settings.frame_interval, Duration::from_millis(50));
+        assert_eq!(settings.glyph_sheet, Some(PathBuf::from("captures/glyphs.png")));
+        assert_eq!((settings.layout, settings.physics), (Layout::default(), Physics::default()));
+    }
+
+    #[test]
+    fn invalid_settings_test() {
+        let invalid_key = |settings: &str| match Settings::parse(settings) {
+            Err(SettingsError::Invalid { key, .. }) => key,
+            other => panic!("expected an invalid setting, got {other:?}")
+        };
+
+        assert_eq!(invalid_key("[overlay]\ntrajectory_colour = \"red\""), "overlay.trajectory_colour");
+        assert_eq!(invalid_key("[overlay]\ntrajectory_width = 0"), "overlay.trajectory_width");
+        assert_eq!(invalid_key("[timing]\nframe_interval_ms = 0"), "timing.frame_interval_ms");
+        assert_eq!(invalid_key("[detection]\nmenu_bar_fraction = 1.5"), "detection.menu_bar_fraction");
+        assert_eq!(invalid_key("[physics]\npath_constant = -3"), "physics.path_constant");
+
+        // misspelt and mistyped settings aren't ignored
+        assert!(matches!(Settings::parse("[overlay]\ntrajectory_color = \"#ffffff\""), Err(SettingsError::Parse(_))));
+        assert!(matches!(Settings::parse("[timing]\nframe_interval_ms = \"fast\""), Err(SettingsError::Parse(_))));
+    }
+}
@@ -0,0 +1,171 @@
+use std::ops::RangeInclusive;
+
+use crate::Coordinate;
+use crate::tank::Tank;
+use crate::terrain::Terrain;
+use crate::viewport::Viewport;
+
+/// The powers that can be selected in game.
+pub const POWER_RANGE: RangeInclusive<u8> = 0..=100;
+/// The angles that can be selected in game, for either direction. Negative angles aim below level.
+pub const ANGLE_RANGE: RangeInclusive<i8> = -90..=90;
+/// Shots at this angle or above are considered high arcs.
+const HIGH_ARC_ANGLE: i8 = 45;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArcPreference {
+    /// Rank solutions by miss distance only
+    #[default]
+    Any,
+    /// Rank high arcs before low arcs
+    High,
+    /// Rank low arcs before high arcs
+    Low
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SolverOptions {
+    /// The furthest, in pixels, the trajectory can pass from the target to be a solution
+    pub tolerance: f32,
+    pub arc: ArcPreference
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self { tolerance: 5.0, arc: ArcPreference::Any }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Solution {
+    pub power: u8,
+    pub angle: i8,
+    /// The closest the trajectory passes to the target, in pixels
+    pub miss_distance: f32
+}
+
+impl Solution {
+    pub fn is_high_arc(&self) -> bool {
+        self.angle >= HIGH_ARC_ANGLE
+    }
+}
+
+fn distance_to_segment(point: Coordinate<i32>, start: Coordinate<i32>, end: Coordinate<i32>) -> f32 {
+    let (px, py) = ((point.0 - start.0) as f32, (point.1 - start.1) as f32);
+    let (sx, sy) = ((end.0 - start.0) as f32, (end.1 - start.1) as f32);
+
+    let length_squared = sx*sx + sy*sy;
+    let along = if length_squared == 0.0 { 0.0 } else { ((px*sx + py*sy) / length_squared).clamp(0.0, 1.0) };
+
+    ((px - along*sx).powi(2) + (py - along*sy).powi(2)).sqrt()
+}
+
+/// The closest the tank's trajectory passes to `target`, in pixels.
+///
+/// The trajectory stops where it hits `terrain`, so a target behind a hill is missed.
+pub fn miss_distance(tank: &Tank, target: Coordinate<i32>, viewport: &Viewport, terrain: Option<&Terrain>) -> f32 {
+    let trajectory = tank.trajectory(viewport, terrain, &[]);
+
+    trajectory.segments()
+        .map(|(start, end)| distance_to_segment(target, start, end))
+        .chain(trajectory.points().take(1).map(|start| distance_to_segment(target, start, start)))
+        .fold(f32::MAX, f32::min)
+}
+
+/// Find every power and angle that hits `target`, given the tank's position, wind and direction.
+///
+/// The power and angle of `tank` are ignored. Solutions are ranked by `options.arc`, then by miss distance.
+pub fn solve(tank: &Tank, target: Coordinate<i32>, viewport: &Viewport, terrain: Option<&Terrain>, options: SolverOptions) -> Vec<Solution> {
+    let mut solutions = Vec::new();
+    let mut candidate = tank.clone();
+
+    for power in POWER_RANGE {
+        for angle in ANGLE_RANGE {
+            candidate.power = power;
+            candidate.angle = angle;
+
+            let miss_distance = miss_distance(&candidate, target, viewport, terrain);
+            if miss_distance <= options.tolerance {
+                solutions.push(Solution { power, angle, miss_distance });
+            }
+        }
+    }
+
+    let arc_rank = |solution: &Solution| match options.arc {
+        ArcPreference::Any => 0,
+        ArcPreference::High => !solution.is_high_arc() as u8,
+        ArcPreference::Low => solution.is_high_arc() as u8
+    };
+
+    solutions.sort_by(|a, b| {
+        arc_rank(a).cmp(&arc_rank(b))
+            .then(a.miss_distance.total_cmp(&b.miss_distance))
+    });
+
+    solutions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Size;
+    use crate::viewport::Layout;
+    use crate::tank::Direction;
+
+    const DIMENSIONS: Size<u32> = Size(2560, 1440);
+
+    fn viewport() -> Viewport {
+        Viewport::from_dimensions(DIMENSIONS, Layout::default())
+    }
+
+    fn tank(power: u8, angle: i8) -> Tank {
+        Tank { screen_position: Coordinate(600, 400), ..Tank::new(power, angle, -12, Direction::Right) }
+    }
+
+    #[test]
+    fn solve_finds_shot_test() {
+        let shot = tank(70, 60);
+        let target = shot.curve_points(&viewport())[40];
+
+        let solutions = solve(&shot, target, &viewport(), None, SolverOptions::default());
+
+        assert!(solutions.iter().any(|solution| solution.power == 70 && solution.angle == 60));
+        assert!(solutions.windows(2).all(|pair| pair[0].miss_distance <= pair[1].miss_distance));
+        assert!(solutions.iter().all(|solution| miss_distance(&tank(solution.power, solution.angle), target, &viewport(), None) <= 5.0));
+    }
+
+    #[test]
+    fn arc_preference_test() {
+        let target = tank(80, 30).curve_points(&viewport())[15];
+
+        let high = solve(&tank(0, 0), target, &viewport(), None, SolverOptions { arc: ArcPreference::High, ..Default::default() });
+        let low = solve(&tank(0, 0), target, &viewport(), None, SolverOptions { arc: ArcPreference::Low, ..Default::default() });
+
+        assert!(high.first().unwrap().is_high_arc());
+        assert!(!low.first().unwrap().is_high_arc());
+        assert_eq!(high.len(), low.len());
+    }
+
+    #[test]
+    fn unreachable_target_test() {
+        let solutions = solve(&tank(0, 0), Coordinate(-500, 400), &viewport(), None, SolverOptions::default());
+        assert!(solutions.is_empty());
+    }
+
+    #[test]
+    fn hill_blocks_shot_test() {
+        let shot = tank(70, 60);
+        let target = shot.curve_points(&viewport())[40];
+
+        // a wall reaching the top of the screen halfway to the target
+        let wall = (shot.screen_position.0 as i32 + target.0) / 2;
+        let terrain = Terrain::from_heights((0..DIMENSIONS.0 as i32)
+            .map(|x| Some(if (x - wall).abs() <= 5 { DIMENSIONS.1 } else { 0 }))
+            .collect());
+
+        let solutions = solve(&shot, target, &viewport(), Some(&terrain), SolverOptions::default());
+
+        assert!(miss_distance(&shot, target, &viewport(), Some(&terrain)) > 5.0);
+        assert!(!solutions.iter().any(|solution| solution.power == 70 && solution.angle == 60));
+    }
+}
@@ -1,11 +1,20 @@
-use crate::{Size, Coordinate};
+use std::path::Path;
+
+use crate::{Coordinate, Rect};
+use crate::calibration::{format_profile, parse_profile, CalibrationError};
+use crate::terrain::Terrain;
+use crate::viewport::Viewport;
+use crate::trajectory::Trajectory;
+use crate::weapon::Weapon;
 
 // Constants are on a 2560x1440, 16:9 monitor
-// They are scaled depending on the monitor's dimensions.
-const WIND_CONSTANT: f32 = 0.00364;
-const PATH_CONSTANT: f32 = 3.0183;
+// They are scaled by the size of the game area, see `Viewport`.
+pub const WIND_CONSTANT: f32 = 0.00364;
+pub const PATH_CONSTANT: f32 = 3.0183;
+/// Projectiles still flying after this many units of time are assumed never to land, such as without gravity.
+pub const MAX_FLIGHT_STEPS: usize = 10_000;
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum Direction {
     Left,
     Right
@@ -20,39 +29,230 @@ impl Direction {
     }
 }
 
+/// What happens to a projectile that reaches the left, right or top of the game area, which depends on the map.
+/// Projectiles always leave through the bottom.
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq)]
+pub enum Border {
+    /// Leaves the game area, except through the top, which it falls back from
+    #[default]
+    Stop,
+    /// Bounces off the left, right and top
+    Bounce,
+    /// Leaves through the left or right and comes back in on the other side
+    Wrap
+}
+
+impl Border {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "stop" => Some(Border::Stop),
+            "bounce" => Some(Border::Bounce),
+            "wrap" => Some(Border::Wrap),
+            _ => None
+        }
+    }
+}
+
+/// The constants of the projectile physics, on a 2560x1440 monitor.
+/// Calibrated values can be saved to and loaded from a profile, see `calibration`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Physics {
+    pub wind_constant: f32,
+    /// The strength of gravity
+    pub path_constant: f32
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self { wind_constant: WIND_CONSTANT, path_constant: PATH_CONSTANT }
+    }
+}
+
+impl Physics {
+    /// Load a calibration profile.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CalibrationError> {
+        parse_profile(&std::fs::read_to_string(path)?)
+    }
+
+    /// Save as a calibration profile.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CalibrationError> {
+        Ok(std::fs::write(path, format_profile(self))?)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Tank {
     pub screen_position: Coordinate<u32>,
     pub angle: i8,
     pub direction: Direction,
     pub power: u8,
-    pub wind: i8
+    pub wind: i8,
+    pub physics: Physics,
+    pub weapon: Weapon,
+    pub border: Border
+}
+
+/// An edge of the game area.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom
+}
+
+impl Edge {
+    /// Whether `point` is outside of `area` beyond this edge.
+    pub fn is_beyond(&self, point: Coordinate<i32>, area: Rect) -> bool {
+        match self {
+            Edge::Left => point.0 <= area.origin.0 as i32,
+            Edge::Right => point.0 > area.end().0 as i32,
+            Edge::Top => point.1 >= area.end().1 as i32,
+            Edge::Bottom => point.1 <= area.origin.1 as i32
+        }
+    }
+
+    /// The edge of `area` that `point` has left through, if it has.
+    /// The top is only an edge when projectiles bounce off it.
+    pub fn crossed(point: Coordinate<i32>, area: Rect, border: Border) -> Option<Edge> {
+        let edges: &[Edge] = match border {
+            Border::Bounce => &[Edge::Bottom, Edge::Left, Edge::Right, Edge::Top],
+            _ => &[Edge::Bottom, Edge::Left, Edge::Right]
+        };
+
+        edges.iter().copied().find(|edge| edge.is_beyond(point, area))
+    }
+}
+
+/// A projectile in free flight, in pixels and the game's time units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ballistic {
+    pub start: Coordinate<i32>,
+    pub velocity: (f32, f32),
+    pub acceleration: (f32, f32)
+}
+
+impl Ballistic {
+    /// The exact offset of the projectile from its start at `t`, in pixels.
+    pub fn offset(&self, t: f32) -> (f32, f32) {
+        (
+            self.velocity.0 * t + 0.5 * self.acceleration.0 * t.powi(2),
+            self.velocity.1 * t + 0.5 * self.acceleration.1 * t.powi(2)
+        )
+    }
+
+    pub fn position(&self, t: f32) -> Coordinate<i32> {
+        let (x, y) = self.offset(t);
+        Coordinate(x as i32 + self.start.0, y as i32 + self.start.1)
+    }
+
+    pub fn velocity_at(&self, t: f32) -> (f32, f32) {
+        (self.velocity.0 + self.acceleration.0 * t, self.velocity.1 + self.acceleration.1 * t)
+    }
+
+    /// The time the projectile stops rising, which may be before it starts.
+    pub fn apex_time(&self) -> f32 {
+        if self.acceleration.1 == 0.0 { 0.0 } else { -self.velocity.1 / self.acceleration.1 }
+    }
+
+    /// Returns the position at each integer t, starting at `start`.
+    /// The points are stopped when they leave the left, right or bottom of `area`, or after `MAX_FLIGHT_STEPS`.
+    /// Like `Weapon::paths`, this ignores the terrain and `Border` mode.
+    pub fn points(&self, area: Rect) -> Vec<Coordinate<i32>> {
+        let mut points = vec![self.start];
+
+        for t in 1..=MAX_FLIGHT_STEPS {
+            let current = self.position(t as f32);
+
+            if Edge::crossed(current, area, Border::Stop).is_some() {
+                break
+            }
+
+            points.push(current);
+        }
+
+        points
+    }
+
+    /// The time between `before` and `after` that the projectile crosses `edge`, to within a hundredth.
+    pub fn crossing_time(&self, edge: Edge, area: Rect, mut before: f32, mut after: f32) -> f32 {
+        while after - before > 0.01 {
+            let middle = (before + after) / 2.0;
+            if edge.is_beyond(self.position(middle), area) {
+                after = middle;
+            } else {
+                before = middle;
+            }
+        }
+
+        after
+    }
 }
 
 impl Tank {
-    pub fn curve_function(&self, t: i32, dimensions: Size<u32>) -> Coordinate<i32> {
-        let x_scale_ratio = dimensions.0 as f32 / 2560.0;
-        let y_scale_ratio = dimensions.1 as f32 / 1440.0;
+    /// A tank firing a shot with the default physics and border, placed at the bottom left until it is found.
+    pub fn new(power: u8, angle: i8, wind: i8, direction: Direction) -> Self {
+        Self {
+            screen_position: Coordinate(0, 0),
+            angle,
+            direction,
+            power,
+            wind,
+            physics: Physics::default(),
+            weapon: Weapon::default(),
+            border: Border::default()
+        }
+    }
+
+    /// The motion of the projectile as it leaves the barrel.
+    pub fn launch(&self, viewport: &Viewport) -> Ballistic {
+        let (x_scale_ratio, y_scale_ratio) = viewport.scale();
 
         let x_power_constant = x_scale_ratio;
         let y_power_constant = y_scale_ratio;
 
-        let wind_constant = WIND_CONSTANT * x_scale_ratio;
-        let gravity_constant = PATH_CONSTANT * y_scale_ratio;
+        let wind_constant = self.physics.wind_constant * self.weapon.wind_multiplier() * x_scale_ratio;
+        let gravity_constant = self.physics.path_constant * y_scale_ratio;
 
         let direction_multiplier = self.direction.as_float_multiplier();
+        let angle = (self.angle as f32).to_radians();
 
-        let x_t = x_power_constant * (self.power as f32) * (self.angle as f32).to_radians().cos();
-        let x_t2 = 0.5 * (self.wind as f32) * wind_constant;
+        Ballistic {
+            start: Coordinate(self.screen_position.0 as i32, self.screen_position.1 as i32),
+            velocity: (
+                direction_multiplier * x_power_constant * (self.power as f32) * angle.cos(),
+                y_power_constant * (self.power as f32) * angle.sin()
+            ),
+            acceleration: (direction_multiplier * (self.wind as f32) * wind_constant, -gravity_constant)
+        }
+    }
+
+    pub fn curve_function(&self, t: i32, viewport: &Viewport) -> Coordinate<i32> {
+        self.launch(viewport).position(t as f32)
+    }
 
-        let y_t = y_power_constant * (self.power as f32) * (self.angle as f32).to_radians().sin();
-        let y_t2 = -0.5 * gravity_constant;
+    /// The exact offset of the projectile from the tank at `t`, in pixels.
+    pub fn curve_offset(&self, t: f32, viewport: &Viewport) -> (f32, f32) {
+        self.launch(viewport).offset(t)
+    }
 
-        let t = t as f32;
-        let x = x_t * t + x_t2 * t.powi(2);
-        let x_directional = x * direction_multiplier;
-        let y = y_t * t + y_t2 * t.powi(2);
+    /// Returns the points of the curve at each integer t, starting at the tank.
+    /// The curve is stopped when it leaves the left, right or bottom of the game area.
+    ///
+    /// This is the flight of the shell as fired, see `trajectory` for what the selected weapon does after and the `Border`.
+    pub fn curve_points(&self, viewport: &Viewport) -> Vec<Coordinate<i32>> {
+        self.launch(viewport).points(viewport.area)
+    }
+
+    /// The radius of the selected weapon's explosion in pixels, scaled like the flight in `launch`.
+    pub fn blast_radius(&self, viewport: &Viewport) -> f32 {
+        // the game area is always 16:9, so both axes are scaled the same
+        let (x_scale_ratio, _) = viewport.scale();
+        self.weapon.blast_radius() * x_scale_ratio
+    }
 
-        Coordinate(x_directional as i32 + self.screen_position.0 as i32, y as i32 + self.screen_position.1 as i32)
+    /// The predicted flight of the selected weapon's projectiles, see `Trajectory::new`.
+    pub fn trajectory(&self, viewport: &Viewport, terrain: Option<&Terrain>, targets: &[Coordinate<u32>]) -> Trajectory {
+        Trajectory::new(self, viewport, terrain, targets)
     }
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use crate::{Coordinate, Size};
+use crate::bitmap::{Bitmap, ARGB};
+use crate::image_processing::Team;
+use crate::viewport::{Layout, Viewport};
+
+/// The largest summed channel difference from the sky colour that is still considered sky.
+pub const SKY_TOLERANCE: u32 = 60;
+/// Pixels scoring at least this for either team are part of a tank, not the ground, see `Team::pixel_score`.
+const TANK_PIXEL_SCORE: f32 = 128.0;
+/// How many columns either side of a point are used to find the slope of the ground.
+const NORMAL_SPAN: i32 = 3;
+
+fn colour_distance(a: ARGB, b: ARGB) -> u32 {
+    a.r.abs_diff(b.r) as u32 + a.g.abs_diff(b.g) as u32 + a.b.abs_diff(b.b) as u32
+}
+
+/// The most common colour along the top row of the game area, which is almost all sky.
+fn sky_colour(bitmap: &Bitmap<ARGB>, viewport: &Viewport) -> Option<ARGB> {
+    let top_row = viewport.area.end().1.checked_sub(1)?;
+    let row = bitmap.inner.get(top_row*bitmap.width..(top_row + 1)*bitmap.width)?;
+
+    let mut counts = HashMap::new();
+    for pixel in &row[viewport.area.origin.0..viewport.area.end().0] {
+        *counts.entry(u32::from(*pixel)).or_insert(0usize) += 1;
+    }
+
+    counts.into_iter().max_by_key(|(colour, count)| (*count, *colour)).map(|(colour, _)| ARGB::from(colour))
+}
+
+/// The height of the ground in each column of the screen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Terrain {
+    /// The y coordinate of the highest ground pixel in each column, relative to bottom left.
+    /// `None` if the column has no ground, such as a gap between hills.
+    heights: Vec<Option<u32>>
+}
+
+impl Terrain {
+    pub fn from_heights(heights: Vec<Option<u32>>) -> Self {
+        Self { heights }
+    }
+
+    /// Extract the ground profile from a captured frame, see `extract_with_sky`.
+    ///
+    /// The sky colour is the most common colour along the top row of the game area.
+    pub fn extract(bitmap: &Bitmap<ARGB>, layout: Layout) -> Self {
+        let viewport = Viewport::new(Size(bitmap.width, bitmap.height()), layout);
+        match sky_colour(bitmap, &viewport) {
+            Some(sky) => Self::extract_with_sky(bitmap, sky, layout),
+            None => Self { heights: vec![None; bitmap.width] }
+        }
+    }
+
+    /// Extract the ground profile from a captured frame, given the colour of the sky.
+    ///
+    /// Each column is scanned upwards from just above the menu bar until the first sky coloured or tank coloured pixel,
+    /// so shading and outlines on the ground don't end it early. A column that is sky just above the menu bar has no ground,
+    /// and one with no sky at all has ground up to the top of the game area. Columns outside the game area have no ground.
+    pub fn extract_with_sky(bitmap: &Bitmap<ARGB>, sky: ARGB, layout: Layout) -> Self {
+        let width = bitmap.width;
+        let viewport = Viewport::new(Size(width, bitmap.height()), layout);
+        let (menu_top, top) = (viewport.menu_bar_top(), viewport.area.end().1);
+
+        let is_ground = |pixel: ARGB| colour_distance(pixel, sky) > SKY_TOLERANCE
+            && Team::ALL.iter().all(|team| team.pixel_score(pixel) < TANK_PIXEL_SCORE);
+
+        let heights = (0..width).map(|x| {
+            if !viewport.contains_column(x as i32) || menu_top >= top {
+                return None
+            }
+
+            let above_ground = (menu_top..top).find(|y| !is_ground(bitmap.inner[y*width + x])).unwrap_or(top);
+            (above_ground > menu_top).then(|| above_ground as u32 - 1)
+        }).collect();
+
+        Self { heights }
+    }
+
+    pub fn heights(&self) -> &[Option<u32>] {
+        &self.heights
+    }
+
+    /// The height of the ground at column `x`, if there is any.
+    pub fn ground_height(&self, x: i32) -> Option<u32> {
+        usize::try_from(x).ok().and_then(|x| self.heights.get(x).copied().flatten())
+    }
+
+    /// The unit vector pointing out of the ground at column `x`, from the slope either side of it.
+    /// Flat ground, or ground with a gap either side, points straight up.
+    pub fn normal(&self, x: i32) -> (f32, f32) {
+        let (Some(left), Some(right)) = (self.ground_height(x - NORMAL_SPAN), self.ground_height(x + NORMAL_SPAN)) else {
+            return (0.0, 1.0)
+        };
+
+        let (dx, dy) = (2.0 * NORMAL_SPAN as f32, right as f32 - left as f32);
+        let length = (dx*dx + dy*dy).sqrt();
+
+        (-dy / length, dx / length)
+    }
+
+    pub fn is_below_ground(&self, point: Coordinate<i32>) -> bool {
+        self.ground_height(point.0).is_some_and(|ground| point.1 <= ground as i32)
+    }
+
+    /// Finds where the line from `from` to `to` first hits the ground, checking every column it passes over.
+    pub fn segment_impact(&self, from: Coordinate<i32>, to: Coordinate<i32>) -> Option<Coordinate<i32>> {
+        let dx = to.0 - from.0;
+        let steps = dx.abs().max(1);
+
+        (1..=steps).find_map(|step| {
+            let x = from.0 + dx.signum()*step;
+            let y = from.1 + ((to.1 - from.1) as f32 * step as f32 / steps as f32).round() as i32;
+
+            self.ground_height(x)
+                .filter(|ground| y <= *ground as i32)
+                .map(|ground| Coordinate(x, ground as i32))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SKY: ARGB = ARGB { r: 120, g: 180, b: 230, a: 255 };
+    const GROUND: ARGB = ARGB { r: 90, g: 60, b: 30, a: 255 };
+
+    fn scene(width: usize, height: usize, ground_heights: &[usize]) -> Vec<ARGB> {
+        (0..width*height).map(|i| {
+            let (x, y) = (i % width, i / width);
+            if ground_heights.get(x).is_some_and(|ground| y <= *ground) { GROUND } else { SKY }
+        }).collect()
+    }
+
+    #[test]
+    fn extract_test() {
+        let (width, height) = (80, 45);
+        let mut pixels = scene(width, height, &[20, 30, 44, 5]);
+        // a tank sitting on the ground isn't part of it
+        pixels[21*width] = ARGB { r: 0, g: 255, b: 0, a: 255 };
+
+        let terrain = Terrain::extract(&Bitmap::new(&mut pixels, width), Layout::default());
+
+        // ground reaching the top of the screen, and a gap down to the menu bar
+        assert_eq!(terrain.heights()[..5], [Some(20), Some(30), Some(44), None, None]);
+
+        // on an ultrawide screen, the pillarbox isn't ground
+        let mut pixels = scene(120, height, &[20; 40]);
+        let terrain = Terrain::extract(&Bitmap::new(&mut pixels, 120), Layout::default());
+        assert_eq!(terrain.heights()[19], None);
+        assert_eq!(terrain.heights()[20], Some(20));
+    }
+
+    #[test]
+    fn extract_shaded_ground_test() {
+        let (width, height) = (80, 45);
+        let mut pixels = scene(width, height, &[25, 25]);
+        for y in 8..=25 {
+            // a gradient down the ground with a dark outline along its top, both far from the colour at its base
+            pixels[y*width] = ARGB { r: 90 + 4*y as u8, g: 60 + 2*y as u8, b: 30, a: 255 };
+            pixels[y*width + 1] = if y >= 24 { ARGB { r: 10, g: 10, b: 10, a: 255 } } else { GROUND };
+        }
+        // a cloud and a HUD element above a gap aren't ground
+        pixels[30*width + 2] = ARGB { r: 250, g: 250, b: 250, a: 255 };
+        pixels[40*width + 3] = ARGB { r: 20, g: 20, b: 20, a: 255 };
+
+        let terrain = Terrain::extract(&Bitmap::new(&mut pixels, width), Layout::default());
+
+        assert_eq!(terrain.heights()[..4], [Some(25), Some(25), None, None]);
+    }
+
+    #[test]
+    fn segment_impact_test() {
+        let terrain = Terrain::from_heights(vec![Some(10); 5].into_iter().chain(vec![Some(40); 5]).collect());
+
+        // the segment from x=2 to x=8 runs into the wall at x=5
+        assert_eq!(terrain.segment_impact(Coordinate(2, 30), Coordinate(8, 30)), Some(Coordinate(5, 40)));
+        assert_eq!(terrain.segment_impact(Coordinate(0, 20), Coordinate(3, 25)), None);
+    }
+}
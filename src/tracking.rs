@@ -0,0 +1,399 @@
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::{Coordinate, Rect, Size};
+use crate::bitmap::ARGB;
+use crate::viewport::Layout;
+use crate::image_processing::{find_tank, find_tank_in_region, find_tanks, tank_size_for_dimensions, Detection, DetectionError, FrameScores, TankCandidate};
+
+/// How long the last known position is reused for after the tank is lost.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(2);
+/// How long the tank can be missing before the tracer gives up.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+/// How far, in tank sizes, the tank is searched for around its predicted position.
+pub const DEFAULT_SEARCH_MARGIN: f32 = 2.0;
+/// The fraction of the difference between the predicted and measured position applied to the position estimate.
+pub const DEFAULT_POSITION_GAIN: f32 = 0.5;
+/// The fraction of the difference between the predicted and measured position applied to the velocity estimate.
+pub const DEFAULT_VELOCITY_GAIN: f32 = 0.2;
+/// How long the tanks found by scanning a whole frame are reused for, as the scan is slow.
+pub const TANK_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackingConfig {
+    pub grace_period: Duration,
+    /// Measured from the last frame the tank was found in, or from the first frame if it never was
+    pub timeout: Duration,
+    pub search_margin: f32,
+    /// Lower values smooth out more jitter, but follow a moving tank more slowly
+    pub position_gain: f32,
+    pub velocity_gain: f32
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: DEFAULT_GRACE_PERIOD,
+            timeout: DEFAULT_TIMEOUT,
+            search_margin: DEFAULT_SEARCH_MARGIN,
+            position_gain: DEFAULT_POSITION_GAIN,
+            velocity_gain: DEFAULT_VELOCITY_GAIN
+        }
+    }
+}
+
+/// A smoothed estimate of the tank's position and velocity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub position: (f32, f32),
+    /// In pixels per second
+    pub velocity: (f32, f32),
+    pub at: Instant
+}
+
+impl Estimate {
+    /// The estimated position at `now`, assuming the tank keeps moving at the same velocity.
+    pub fn predict(&self, now: Instant) -> (f32, f32) {
+        let dt = now.saturating_duration_since(self.at).as_secs_f32();
+        (self.position.0 + self.velocity.0*dt, self.position.1 + self.velocity.1*dt)
+    }
+}
+
+/// Follows the tank across frames with an alpha-beta filter.
+///
+/// The tank is first searched for near its predicted position, so it can't jump to another blob,
+/// falling back to a full scan of the same `FrameScores` if it isn't found there.
+#[derive(Clone, Debug)]
+pub struct Tracker {
+    pub config: TrackingConfig,
+    estimate: Option<Estimate>
+}
+
+impl Tracker {
+    pub fn new(config: TrackingConfig) -> Self {
+        Self { config, estimate: None }
+    }
+
+    pub fn estimate(&self) -> Option<Estimate> {
+        self.estimate
+    }
+
+    /// The estimated velocity of the tank in pixels per second.
+    pub fn velocity(&self) -> Option<(f32, f32)> {
+        self.estimate.map(|estimate| estimate.velocity)
+    }
+
+    /// The area searched around `predicted` before falling back to a full scan.
+    fn search_window(&self, predicted: (f32, f32), tank_size: Size<usize>) -> Rect {
+        let half_width = tank_size.0 as f32 * (0.5 + self.config.search_margin);
+        let half_height = tank_size.1 as f32 * (0.5 + self.config.search_margin);
+
+        let left = (predicted.0 - half_width).max(0.0) as usize;
+        let bottom = (predicted.1 - half_height).max(0.0) as usize;
+        let right = (predicted.0 + half_width).max(0.0) as usize;
+        let top = (predicted.1 + half_height).max(0.0) as usize;
+
+        Rect { origin: Coordinate(left, bottom), size: Size(right - left, top - bottom) }
+    }
+
+    /// Locate the tank in a frame captured at `now`, with tanks the size given by `layout`.
+    ///
+    /// The returned detection's position is the smoothed estimate, rather than the raw match.
+    pub fn detect(&mut self, scores: &FrameScores, layout: Layout, now: Instant) -> Result<Detection, DetectionError> {
+        let tank_size = tank_size_for_dimensions(scores.dimensions(), layout);
+
+        let nearby = self.estimate.and_then(|estimate| {
+            let region = self.search_window(estimate.predict(now), tank_size);
+            find_tank_in_region(scores, region, layout).ok()
+        });
+
+        let mut detection = match nearby {
+            Some(detection) => detection,
+            None => {
+                // the tank has moved further than expected, or has never been found, so don't smooth from the old estimate
+                let detection = find_tank(scores, layout)?;
+                self.estimate = None;
+                detection
+            }
+        };
+
+        let estimate = self.correct(detection.position, now);
+        detection.position = Coordinate(estimate.position.0.round() as u32, estimate.position.1.round() as u32);
+
+        Ok(detection)
+    }
+
+    /// Update the estimate with a position measured at `now`.
+    pub fn correct(&mut self, measured: Coordinate<u32>, now: Instant) -> Estimate {
+        let measured = (measured.0 as f32, measured.1 as f32);
+
+        let estimate = match self.estimate {
+            None => Estimate { position: measured, velocity: (0.0, 0.0), at: now },
+            Some(previous) => {
+                let dt = now.saturating_duration_since(previous.at).as_secs_f32();
+                let predicted = previous.predict(now);
+                let residual = (measured.0 - predicted.0, measured.1 - predicted.1);
+
+                let (alpha, beta) = (self.config.position_gain, self.config.velocity_gain);
+                let velocity = if dt > 0.0 {
+                    (previous.velocity.0 + beta*residual.0/dt, previous.velocity.1 + beta*residual.1/dt)
+                } else {
+                    previous.velocity
+                };
+
+                Estimate {
+                    position: (predicted.0 + alpha*residual.0, predicted.1 + alpha*residual.1),
+                    velocity,
+                    at: now
+                }
+            }
+        };
+
+        self.estimate = Some(estimate);
+        estimate
+    }
+}
+
+/// The state of the tank after a frame has been processed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrackingStatus {
+    /// The tank was found in this frame
+    Found { position: Coordinate<u32> },
+    /// The tank wasn't found, but is still within the grace period so its last known position is used
+    Holding { position: Coordinate<u32>, missing_for: Duration, reason: DetectionError },
+    /// The tank hasn't been found for longer than the grace period, so there is no position to draw from
+    Lost { missing_for: Duration, reason: DetectionError }
+}
+
+impl TrackingStatus {
+    /// The position the trajectory should be drawn from, if any.
+    pub fn position(&self) -> Option<Coordinate<u32>> {
+        match self {
+            Self::Found { position } | Self::Holding { position, .. } => Some(*position),
+            Self::Lost { .. } => None
+        }
+    }
+
+    /// The colour of the status indicator drawn on the overlay.
+    pub fn indicator_colour(&self) -> ARGB {
+        match self {
+            Self::Found { .. } => ARGB { r: 60, g: 200, b: 60, a: 255 },
+            Self::Holding { .. } => ARGB { r: 230, g: 170, b: 30, a: 255 },
+            Self::Lost { .. } => ARGB { r: 220, g: 50, b: 50, a: 255 }
+        }
+    }
+
+    /// A short description of the status, shown next to the indicator.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Found { .. } => "Tank found".to_string(),
+            Self::Holding { missing_for, .. } => format!("Tank missing for {:.1}s", missing_for.as_secs_f32()),
+            Self::Lost { missing_for, .. } => format!("Tank lost for {:.1}s", missing_for.as_secs_f32())
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+#[error("Tank not found for {missing_for:?}, giving up ({reason})")]
+pub struct TrackingTimeout {
+    pub missing_for: Duration,
+    pub reason: DetectionError
+}
+
+/// Turns per-frame detection results into a tracking status, so a tank hidden for a few frames isn't fatal.
+#[derive(Clone, Debug)]
+pub struct TrackingState {
+    pub config: TrackingConfig,
+    pub tracker: Tracker,
+    last_position: Option<Coordinate<u32>>,
+    /// When the tank was last found, or when tracking started
+    last_seen: Option<Instant>,
+    /// Every tank found in the last frame scanned, and when it was captured
+    tanks: Option<(Vec<TankCandidate>, Instant)>
+}
+
+impl TrackingState {
+    pub fn new(config: TrackingConfig) -> Self {
+        Self { config, tracker: Tracker::new(config), last_position: None, last_seen: None, tanks: None }
+    }
+
+    /// Locate the tank in a frame captured at `now`, see `Tracker::detect` and `update`.
+    pub fn track(&mut self, scores: &FrameScores, layout: Layout, now: Instant) -> Result<TrackingStatus, TrackingTimeout> {
+        let detection = self.tracker.detect(scores, layout, now);
+        self.update(detection, now)
+    }
+
+    /// Every tank in a frame captured at `now`, with `status`, see `find_tanks`.
+    ///
+    /// The whole frame is only scanned if the tank was found in it and the last scan is older than `TANK_SCAN_INTERVAL`,
+    /// otherwise the tanks from the last scan are reused.
+    pub fn tanks(&mut self, scores: &FrameScores, layout: Layout, status: &TrackingStatus, now: Instant) -> &[TankCandidate] {
+        let stale = self.tanks.as_ref().is_none_or(|(_, scanned)| now.duration_since(*scanned) >= TANK_SCAN_INTERVAL);
+        if stale && matches!(status, TrackingStatus::Found { .. }) {
+            self.tanks = Some((find_tanks(scores, layout), now));
+        }
+
+        self.tanks.as_ref().map_or(&[], |(tanks, _)| tanks)
+    }
+
+    /// The last position the tank was found at.
+    pub fn last_position(&self) -> Option<Coordinate<u32>> {
+        self.last_position
+    }
+
+    /// Update the state with the detection result of a frame captured at `now`.
+    ///
+    /// Fails once the tank has been missing for longer than the timeout.
+    pub fn update(&mut self, detection: Result<Detection, DetectionError>, now: Instant) -> Result<TrackingStatus, TrackingTimeout> {
+        let reason = match detection {
+            Ok(detection) => {
+                self.last_position = Some(detection.position);
+                self.last_seen = Some(now);
+                return Ok(TrackingStatus::Found { position: detection.position })
+            },
+            Err(reason) => reason
+        };
+
+        let missing_for = now.duration_since(*self.last_seen.get_or_insert(now));
+
+        if missing_for > self.config.timeout {
+            return Err(TrackingTimeout { missing_for, reason })
+        }
+
+        match self.last_position {
+            Some(position) if missing_for <= self.config.grace_period => Ok(TrackingStatus::Holding { position, missing_for, reason }),
+            _ => Ok(TrackingStatus::Lost { missing_for, reason })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap::Bitmap;
+
+    const CONFIG: TrackingConfig = TrackingConfig {
+        grace_period: Duration::from_secs(1),
+        timeout: Duration::from_secs(5),
+        search_margin: DEFAULT_SEARCH_MARGIN,
+        position_gain: DEFAULT_POSITION_GAIN,
+        velocity_gain: DEFAULT_VELOCITY_GAIN
+    };
+    const MISSING: DetectionError = DetectionError::BelowThreshold { confidence: 0.0, threshold: 0.1 };
+
+    fn found(x: u32) -> Result<Detection, DetectionError> {
+        let window = Rect { origin: Coordinate(x as usize, 10), size: Size(1, 1) };
+        Ok(Detection { position: Coordinate(x, 10), window, score: 255.0, confidence: 1.0 })
+    }
+
+    #[test]
+    fn grace_period_test() {
+        let start = Instant::now();
+        let mut state = TrackingState::new(CONFIG);
+
+        assert_eq!(state.update(found(5), start), Ok(TrackingStatus::Found { position: Coordinate(5, 10) }));
+
+        let holding = state.update(Err(MISSING), start + Duration::from_millis(500)).unwrap();
+        assert_eq!(holding.position(), Some(Coordinate(5, 10)));
+        assert!(matches!(holding, TrackingStatus::Holding { .. }));
+
+        let lost = state.update(Err(MISSING), start + Duration::from_secs(2)).unwrap();
+        assert_eq!(lost, TrackingStatus::Lost { missing_for: Duration::from_secs(2), reason: MISSING });
+
+        // finding the tank again resets the timers
+        let refound = start + Duration::from_secs(4);
+        assert!(state.update(found(8), refound).is_ok());
+        let holding = state.update(Err(MISSING), refound + Duration::from_millis(900)).unwrap();
+        assert_eq!(holding.position(), Some(Coordinate(8, 10)));
+    }
+
+    #[test]
+    fn timeout_test() {
+        let start = Instant::now();
+        let mut state = TrackingState::new(CONFIG);
+
+        // never found, so timed from the first frame
+        assert!(matches!(state.update(Err(MISSING), start), Ok(TrackingStatus::Lost { .. })));
+        assert!(state.update(Err(MISSING), start + Duration::from_secs(5)).is_ok());
+
+        let error = state.update(Err(MISSING), start + Duration::from_secs(6)).unwrap_err();
+        assert_eq!(error.missing_for, Duration::from_secs(6));
+        assert_eq!(state.last_position(), None);
+    }
+
+    fn frame(tanks: &[Coordinate<usize>]) -> Vec<ARGB> {
+        let mut pixels = vec![ARGB { r: 40, g: 40, b: 40, a: 255 }; 640*360];
+        for corner in tanks {
+            for y in corner.1..corner.1+7 {
+                pixels[y*640 + corner.0..y*640 + corner.0 + 11].fill(ARGB { r: 0, g: 255, b: 0, a: 255 });
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn smoothing_test() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(CONFIG);
+
+        tracker.correct(Coordinate(100, 100), start);
+        // jitter back and forth is damped
+        let estimate = tracker.correct(Coordinate(104, 100), start + Duration::from_millis(100));
+        assert!(estimate.position.0 > 100.0 && estimate.position.0 < 104.0);
+
+        // a steadily moving tank is followed with the right velocity
+        let mut tracker = Tracker::new(CONFIG);
+        for frame in 0..50 {
+            tracker.correct(Coordinate(100 + 2*frame, 100), start + Duration::from_millis(100*frame as u64));
+        }
+        let velocity = tracker.velocity().unwrap();
+        assert!((velocity.0 - 20.0).abs() < 0.5, "velocity {velocity:?}");
+        assert!(velocity.1.abs() < 0.01);
+    }
+
+    #[test]
+    fn region_search_test() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(CONFIG);
+
+        let mut pixels = frame(&[Coordinate(100, 200)]);
+        let detection = tracker.detect(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default(), start).unwrap();
+        assert_eq!(detection.position, Coordinate(105, 203));
+
+        // a second tank appears, which would be ambiguous in a full scan, but only the nearby one is searched
+        let mut pixels = frame(&[Coordinate(102, 200), Coordinate(400, 200)]);
+        let now = start + Duration::from_millis(100);
+        let detection = tracker.detect(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default(), now).unwrap();
+        assert!((105..=107).contains(&detection.position.0));
+        assert!(tracker.velocity().unwrap().0 > 0.0);
+
+        // the tank moves out of the search region, so a full scan finds it and the estimate restarts
+        let mut pixels = frame(&[Coordinate(400, 200)]);
+        let now = now + Duration::from_millis(100);
+        let detection = tracker.detect(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default(), now).unwrap();
+        assert_eq!(detection.position, Coordinate(405, 203));
+        assert_eq!(tracker.velocity(), Some((0.0, 0.0)));
+    }
+
+    #[test]
+    fn tank_scan_test() {
+        let start = Instant::now();
+        let mut state = TrackingState::new(CONFIG);
+        let found = TrackingStatus::Found { position: Coordinate(105, 203) };
+        let holding = TrackingStatus::Holding { position: Coordinate(105, 203), missing_for: Duration::ZERO, reason: MISSING };
+
+        // nothing is scanned until the tank is found
+        let mut pixels = frame(&[Coordinate(100, 200), Coordinate(400, 200)]);
+        assert!(state.tanks(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default(), &holding, start).is_empty());
+        assert_eq!(state.tanks(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default(), &found, start).len(), 2);
+
+        // the scan is reused until it is stale
+        let mut pixels = frame(&[Coordinate(100, 200)]);
+        let soon = start + TANK_SCAN_INTERVAL / 2;
+        assert_eq!(state.tanks(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default(), &found, soon).len(), 2);
+        let later = start + TANK_SCAN_INTERVAL;
+        assert_eq!(state.tanks(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default(), &holding, later).len(), 2);
+        assert_eq!(state.tanks(&FrameScores::new(&Bitmap::new(&mut pixels, 640)), Layout::default(), &found, later).len(), 1);
+    }
+}
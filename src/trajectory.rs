@@ -0,0 +1,234 @@
+use crate::Coordinate;
+use crate::tank::Tank;
+use crate::terrain::Terrain;
+use crate::viewport::Viewport;
+use crate::weapon::ProjectilePath;
+
+/// The predicted flight of every projectile fired by a tank, independent of how it is drawn.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trajectory {
+    paths: Vec<ProjectilePath>
+}
+
+/// A point a whole number of steps along a path, see `Trajectory::along`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArcPoint {
+    /// The index of the path in `Trajectory::paths`
+    pub path: usize,
+    /// How far along the path the point is, in pixels
+    pub distance: f32,
+    /// Relative to bottom left
+    pub position: (f32, f32)
+}
+
+impl ArcPoint {
+    pub fn coordinate(&self) -> Coordinate<i32> {
+        Coordinate(self.position.0.round() as i32, self.position.1.round() as i32)
+    }
+}
+
+fn length(from: Coordinate<i32>, to: Coordinate<i32>) -> f32 {
+    ((to.0 - from.0) as f32).hypot((to.1 - from.1) as f32)
+}
+
+impl Trajectory {
+    /// Predict the flight of the projectiles fired by `tank`'s weapon, see `Weapon::paths`.
+    pub fn new(tank: &Tank, viewport: &Viewport, terrain: Option<&Terrain>, targets: &[Coordinate<u32>]) -> Self {
+        Self { paths: tank.weapon.paths(tank, viewport, terrain, targets) }
+    }
+
+    pub fn from_paths(paths: Vec<ProjectilePath>) -> Self {
+        Self { paths }
+    }
+
+    pub fn paths(&self) -> &[ProjectilePath] {
+        &self.paths
+    }
+
+    /// Every sampled point, of every path.
+    pub fn points(&self) -> impl Iterator<Item = Coordinate<i32>> + '_ {
+        self.paths.iter().flat_map(|path| path.points.iter().copied())
+    }
+
+    /// The straight lines between consecutive points of each path.
+    pub fn segments(&self) -> impl Iterator<Item = (Coordinate<i32>, Coordinate<i32>)> + '_ {
+        self.paths.iter().flat_map(|path| path.points.windows(2).map(|segment| (segment[0], segment[1])))
+    }
+
+    /// How long until the last projectile explodes or leaves the game area.
+    pub fn time_of_flight(&self) -> f32 {
+        self.paths.iter().map(|path| path.end_time).fold(0.0, f32::max)
+    }
+
+    /// The highest point reached by any projectile.
+    pub fn apex(&self) -> Option<Coordinate<i32>> {
+        self.points().max_by_key(|point| point.1)
+    }
+
+    /// Where the first projectile to explode does, if any explode before leaving the game area.
+    pub fn impact(&self) -> Option<Coordinate<i32>> {
+        self.paths.iter()
+            .filter(|path| path.impact.is_some())
+            .min_by(|a, b| a.end_time.total_cmp(&b.end_time))
+            .and_then(|path| path.impact)
+    }
+
+    /// Where the shot first explodes or, if nothing explodes, where the last projectile leaves the game area.
+    pub fn landing(&self) -> Option<Coordinate<i32>> {
+        self.impact().or_else(|| {
+            self.paths.iter()
+                .max_by(|a, b| a.end_time.total_cmp(&b.end_time))
+                .and_then(|path| path.points.last().copied())
+        })
+    }
+
+    /// Where every projectile that explodes before leaving the game area does.
+    pub fn impacts(&self) -> Vec<Coordinate<i32>> {
+        self.paths.iter().filter_map(|path| path.impact).collect()
+    }
+
+    /// The points every `spacing` pixels along each path, starting at the start of each path.
+    ///
+    /// Panics if `spacing` isn't positive.
+    pub fn along(&self, spacing: f32) -> ArcLengthIter<'_> {
+        assert!(spacing > 0.0, "the spacing must be positive");
+        ArcLengthIter { paths: &self.paths, spacing, path: 0, segment: 0, segment_start: 0.0, next_distance: 0.0 }
+    }
+
+    /// The solid dashes of a dotted line along each path, as (start, end) pairs, see `draw::draw_trajectory`.
+    /// Dashes and the gaps between them are both `dash_length` pixels long, measured along the path,
+    /// except for a dash cut short by the end of its path.
+    pub fn dashes(&self, dash_length: f32) -> Vec<(Coordinate<i32>, Coordinate<i32>)> {
+        let points: Vec<ArcPoint> = self.along(dash_length).collect();
+
+        points.iter().enumerate()
+            .filter(|(_, start)| ((start.distance / dash_length).round() as u32).is_multiple_of(2))
+            .filter_map(|(i, start)| {
+                let end = match points.get(i + 1) {
+                    Some(next) if next.path == start.path => next.coordinate(),
+                    _ => *self.paths[start.path].points.last()?
+                };
+                (end != start.coordinate()).then_some((start.coordinate(), end))
+            })
+            .collect()
+    }
+}
+
+/// Iterates over evenly spaced points along the paths of a `Trajectory`, see `Trajectory::along`.
+pub struct ArcLengthIter<'a> {
+    paths: &'a [ProjectilePath],
+    spacing: f32,
+    path: usize,
+    /// The index of the first point of the current segment
+    segment: usize,
+    /// How far along the path the current segment starts
+    segment_start: f32,
+    next_distance: f32
+}
+
+impl Iterator for ArcLengthIter<'_> {
+    type Item = ArcPoint;
+
+    fn next(&mut self) -> Option<ArcPoint> {
+        loop {
+            let points = &self.paths.get(self.path)?.points;
+
+            let (Some(from), Some(to)) = (points.get(self.segment), points.get(self.segment + 1)) else {
+                self.path += 1;
+                self.segment = 0;
+                self.segment_start = 0.0;
+                self.next_distance = 0.0;
+                continue
+            };
+
+            let segment_length = length(*from, *to);
+            if self.next_distance > self.segment_start + segment_length {
+                self.segment += 1;
+                self.segment_start += segment_length;
+                continue
+            }
+
+            let fraction = if segment_length == 0.0 { 0.0 } else { (self.next_distance - self.segment_start) / segment_length };
+            let point = ArcPoint {
+                path: self.path,
+                distance: self.next_distance,
+                position: (from.0 as f32 + fraction * (to.0 - from.0) as f32, from.1 as f32 + fraction * (to.1 - from.1) as f32)
+            };
+
+            self.next_distance += self.spacing;
+            return Some(point)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Size;
+    use crate::viewport::Layout;
+    use crate::tank::{Direction, Physics};
+
+    const DIMENSIONS: Size<u32> = Size(2560, 1440);
+
+    fn viewport() -> Viewport {
+        Viewport::from_dimensions(DIMENSIONS, Layout::default())
+    }
+
+    #[test]
+    fn measurements_test() {
+        let tank = Tank { screen_position: Coordinate(300, 400), ..Tank::new(60, 45, 0, Direction::Right) };
+        let terrain = Terrain::from_heights(vec![Some(400); DIMENSIONS.0 as usize]);
+        let trajectory = Trajectory::new(&tank, &viewport(), Some(&terrain), &[]);
+
+        // without wind, a shot landing at the height it was fired from is symmetric about its apex
+        let (vx, vy) = tank.launch(&viewport()).velocity;
+        let gravity = Physics::default().path_constant;
+        let time_of_flight = 2.0 * vy / gravity;
+        assert!((trajectory.time_of_flight() - time_of_flight).abs() <= 1.0);
+
+        // the apex is the highest sample, which is up to half a unit of time either side of the true apex
+        let apex = trajectory.apex().unwrap();
+        assert!((apex.0 as f32 - (300.0 + vx * vy / gravity)).abs() <= vx / 2.0 + 1.0);
+        assert!((apex.1 as f32 - (400.0 + vy * vy / (2.0 * gravity))).abs() <= 2.0);
+
+        let impact = trajectory.impact().unwrap();
+        assert_eq!(impact.1, 400);
+        assert!((impact.0 as f32 - (300.0 + vx * time_of_flight)).abs() <= vx);
+        assert_eq!(trajectory.impacts(), [impact]);
+    }
+
+    #[test]
+    fn along_test() {
+        let trajectory = Trajectory::from_paths(vec![
+            ProjectilePath::through(&[(0, 0), (3, 4), (3, 14)], false, 2.0),
+            ProjectilePath::through(&[(100, 100), (100, 107)], true, 1.0)
+        ]);
+
+        assert_eq!(trajectory.impact(), Some(Coordinate(100, 107)));
+        assert_eq!(trajectory.time_of_flight(), 2.0);
+
+        // each path starts from a distance of zero
+        let points: Vec<_> = trajectory.along(4.0).map(|point| (point.path, point.distance, point.coordinate())).collect();
+        assert_eq!(points, [
+            (0, 0.0, Coordinate(0, 0)),
+            (0, 4.0, Coordinate(2, 3)),
+            (0, 8.0, Coordinate(3, 7)),
+            (0, 12.0, Coordinate(3, 11)),
+            (1, 0.0, Coordinate(100, 100)),
+            (1, 4.0, Coordinate(100, 104))
+        ]);
+
+        assert_eq!(trajectory.dashes(4.0), [
+            (Coordinate(0, 0), Coordinate(2, 3)),
+            (Coordinate(3, 7), Coordinate(3, 11)),
+            (Coordinate(100, 100), Coordinate(100, 104))
+        ]);
+
+        // the last dash of the first path is cut short where it ends
+        assert_eq!(trajectory.dashes(5.0), [
+            (Coordinate(0, 0), Coordinate(3, 4)),
+            (Coordinate(3, 9), Coordinate(3, 14)),
+            (Coordinate(100, 100), Coordinate(100, 105))
+        ]);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{Coordinate, Rect, Size};
+use crate::image_processing::{MENU_BAR, TANK_HEIGHT_FRACTION, TANK_WIDTH_FRACTION};
+
+/// The resolution the game's constants were measured at. The game is always drawn at this aspect ratio.
+pub const REFERENCE_SIZE: Size<usize> = Size(2560, 1440);
+
+/// The proportions of the game's layout that detection relies on, as fractions of the game area.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub tank_width: f32,
+    pub tank_height: f32,
+    /// The height of the menu bar along the bottom
+    pub menu_bar: f32
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self { tank_width: TANK_WIDTH_FRACTION, tank_height: TANK_HEIGHT_FRACTION, menu_bar: MENU_BAR }
+    }
+}
+
+/// Where the game is drawn on the screen.
+///
+/// The game keeps its 16:9 aspect ratio, so on wider screens it is pillarboxed and on taller screens it is letterboxed.
+/// Every fraction of the screen (tank size, menu bar, HUD regions) is a fraction of the game area, not of the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub screen: Size<usize>,
+    /// The area the game is drawn in, relative to the bottom left of the screen. It doesn't depend on the layout.
+    pub area: Rect,
+    pub layout: Layout
+}
+
+impl Viewport {
+    pub fn new(screen: Size<usize>, layout: Layout) -> Self {
+        let (width, height) = (screen.0, screen.1);
+
+        // compare width/height with 16/9 without rounding, then fit the largest 16:9 rectangle in the centre
+        let area = if width * REFERENCE_SIZE.1 > height * REFERENCE_SIZE.0 {
+            let area_width = ((height * REFERENCE_SIZE.0) as f32 / REFERENCE_SIZE.1 as f32).round() as usize;
+            Rect { origin: Coordinate((width - area_width) / 2, 0), size: Size(area_width, height) }
+        } else {
+            let area_height = ((width * REFERENCE_SIZE.1) as f32 / REFERENCE_SIZE.0 as f32).round() as usize;
+            Rect { origin: Coordinate(0, (height - area_height) / 2), size: Size(width, area_height) }
+        };
+
+        Self { screen, area, layout }
+    }
+
+    pub fn from_dimensions(dimensions: Size<u32>, layout: Layout) -> Self {
+        Self::new(Size(dimensions.0 as usize, dimensions.1 as usize), layout)
+    }
+
+    /// The scale of the game area compared to `REFERENCE_SIZE`, in each axis.
+    pub fn scale(&self) -> (f32, f32) {
+        (self.area.size.0 as f32 / REFERENCE_SIZE.0 as f32, self.area.size.1 as f32 / REFERENCE_SIZE.1 as f32)
+    }
+
+    /// The rectangle between two fractions of the game area, relative to its bottom left.
+    pub fn region(&self, from: (f32, f32), to: (f32, f32)) -> Rect {
+        let scale = |fraction: f32, length: usize| (fraction * length as f32) as usize;
+        let origin = Coordinate(self.area.origin.0 + scale(from.0, self.area.size.0), self.area.origin.1 + scale(from.1, self.area.size.1));
+        let end = Coordinate(self.area.origin.0 + scale(to.0, self.area.size.0), self.area.origin.1 + scale(to.1, self.area.size.1));
+
+        Rect { origin, size: Size(end.0 - origin.0, end.1 - origin.1) }
+    }
+
+    /// The first row above the menu bar.
+    pub fn menu_bar_top(&self) -> usize {
+        self.area.origin.1 + (self.area.size.1 as f32 * self.layout.menu_bar) as usize
+    }
+
+    /// The part of the game area above the menu bar, where tanks and terrain are.
+    pub fn play_area(&self) -> Rect {
+        let menu_bar_top = self.menu_bar_top();
+        Rect {
+            origin: Coordinate(self.area.origin.0, menu_bar_top),
+            size: Size(self.area.size.0, self.area.end().1 - menu_bar_top)
+        }
+    }
+
+    pub fn tank_size(&self) -> Size<usize> {
+        let width = self.area.size.0 as f32 * self.layout.tank_width;
+        let height = self.area.size.1 as f32 * self.layout.tank_height;
+
+        Size(width as usize, height as usize)
+    }
+
+    /// Whether the column at `x` is inside the game area.
+    pub fn contains_column(&self, x: i32) -> bool {
+        x >= self.area.origin.0 as i32 && x < self.area.end().0 as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tank::{Direction, Tank};
+
+    #[test]
+    fn common_resolutions_test() {
+        let cases = [
+            // 16:9 fills the screen
+            (Size(2560, 1440), Rect { origin: Coordinate(0, 0), size: Size(2560, 1440) }),
+            (Size(1920, 1080), Rect { origin: Coordinate(0, 0), size: Size(1920, 1080) }),
+            // 1366x768 is only approximately 16:9
+            (Size(1366, 768), Rect { origin: Coordinate(0, 0), size: Size(1365, 768) }),
+            // 16:10 and 4:3 are letterboxed
+            (Size(2560, 1600), Rect { origin: Coordinate(0, 80), size: Size(2560, 1440) }),
+            (Size(1680, 1050), Rect { origin: Coordinate(0, 52), size: Size(1680, 945) }),
+            (Size(1024, 768), Rect { origin: Coordinate(0, 96), size: Size(1024, 576) }),
+            // ultrawide is pillarboxed
+            (Size(3440, 1440), Rect { origin: Coordinate(440, 0), size: Size(2560, 1440) }),
+            (Size(5120, 1440), Rect { origin: Coordinate(1280, 0), size: Size(2560, 1440) }),
+        ];
+
+        for (screen, area) in cases {
+            let viewport = Viewport::new(screen, Layout::default());
+            assert_eq!(viewport.area, area, "{screen:?}");
+
+            // the game isn't stretched
+            let (x_scale, y_scale) = viewport.scale();
+            assert!((x_scale - y_scale).abs() < 0.002, "{screen:?} is scaled by {x_scale}, {y_scale}");
+        }
+    }
+
+    #[test]
+    fn regions_follow_game_area_test() {
+        let reference = Viewport::new(Size(2560, 1440), Layout::default());
+        let ultrawide = Viewport::new(Size(3440, 1440), Layout::default());
+        let tall = Viewport::new(Size(2560, 1600), Layout::default());
+
+        assert_eq!(ultrawide.tank_size(), reference.tank_size());
+        assert_eq!(tall.tank_size(), reference.tank_size());
+
+        assert_eq!(ultrawide.play_area().origin, Coordinate(440, reference.menu_bar_top()));
+        assert_eq!(tall.menu_bar_top(), reference.menu_bar_top() + 80);
+        assert_eq!(tall.play_area().end(), Coordinate(2560, 1520));
+
+        let region = ultrawide.region((0.5, 0.5), (1.0, 1.0));
+        assert_eq!(region, Rect { origin: Coordinate(1720, 720), size: Size(1280, 720) });
+
+        assert!(!ultrawide.contains_column(439) && ultrawide.contains_column(440) && !ultrawide.contains_column(3000));
+    }
+
+    #[test]
+    fn custom_layout_test() {
+        let layout = Layout { tank_width: 0.01, tank_height: 0.02, menu_bar: 0.1 };
+        let custom = Viewport::new(Size(2560, 1600), layout);
+        let default = Viewport::new(Size(2560, 1600), Layout::default());
+
+        // only the fractions of the layout change, not where the game is drawn
+        assert_eq!((custom.area, custom.scale()), (default.area, default.scale()));
+        assert_eq!(custom.tank_size(), Size(25, 28));
+        assert_eq!(custom.menu_bar_top(), 80 + 144);
+        assert_eq!(custom.play_area(), Rect { origin: Coordinate(0, 224), size: Size(2560, 1296) });
+    }
+
+    #[test]
+    fn curve_is_not_distorted_test() {
+        let tank = Tank { screen_position: Coordinate(1700, 400), ..Tank::new(80, 60, 10, Direction::Right) };
+        let viewport = |screen: Size<usize>| Viewport::new(screen, Layout::default());
+
+        // the same shot on a 16:9 and an ultrawide screen has the same shape, and stops at the pillarbox
+        assert_eq!(tank.curve_offset(20.0, &viewport(Size(3440, 1440))), tank.curve_offset(20.0, &viewport(Size(2560, 1440))));
+        assert!(tank.curve_points(&viewport(Size(3440, 1440))).iter().all(|point| point.0 > 440 && point.0 <= 3000));
+
+        // on a 16:10 screen, the game is scaled by its width, not stretched to fill the height
+        let (x, y) = tank.curve_offset(20.0, &viewport(Size(1920, 1200)));
+        let (reference_x, reference_y) = tank.curve_offset(20.0, &viewport(Size(1920, 1080)));
+        assert!((x - reference_x).abs() < 1e-3 && (y - reference_y).abs() < 1e-3);
+    }
+}
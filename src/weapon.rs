@@ -0,0 +1,628 @@
+use std::f32::consts::{PI, TAU};
+
+use crate::{Coordinate, Rect};
+use crate::tank::{Ballistic, Border, Edge, Tank, MAX_FLIGHT_STEPS};
+use crate::terrain::Terrain;
+use crate::viewport::Viewport;
+
+// The constants below haven't been measured in game. They are approximations that give paths of about the right
+// shape, so the paths of every weapon other than shot and spread are approximate too. That includes heavy, as how
+// much less the wind affects it is a guess, see `Weapon::wind_multiplier`.
+
+/// The fraction of its speed a bouncing projectile keeps after each bounce.
+const BOUNCE_RESTITUTION: f32 = 0.6;
+/// A bouncing projectile slower than this after a bounce explodes instead, in pixels per unit time on a 2560x1440 monitor.
+const MIN_BOUNCE_SPEED: f32 = 5.0;
+/// The horizontal speed each shell of a splitter is pushed apart by, on a 2560x1440 monitor.
+const SPLIT_SPEED: f32 = 10.0;
+/// The fraction of gravity that slows a rolling projectile, like the coefficient of rolling friction.
+const ROLL_FRICTION: f32 = 0.25;
+/// The furthest a homing projectile turns in a unit of time, in radians.
+const HOMING_TURN: f32 = 0.15;
+/// The slowest a homing projectile flies, on a 2560x1440 monitor.
+const MIN_HOMING_SPEED: f32 = 10.0;
+/// Homing projectiles that haven't hit anything after this long are assumed to miss.
+const MAX_HOMING_STEPS: usize = 500;
+/// Projectiles that have bounced off or wrapped around the border this many times are assumed to be stuck.
+const MAX_BORDER_HITS: usize = 8;
+
+/// Every weapon that can be selected, by name.
+pub const CATALOGUE: [(&str, Weapon); 8] = [
+    ("shot", Weapon::Shot),
+    ("three-ball", Weapon::Spread { shells: 3, spread: 5 }),
+    ("five-ball", Weapon::Spread { shells: 5, spread: 5 }),
+    ("heavy", Weapon::Heavy),
+    ("bouncer", Weapon::Bouncer { bounces: 3 }),
+    ("roller", Weapon::Roller),
+    ("splitter", Weapon::Splitter { shells: 3 }),
+    ("homing", Weapon::Homing)
+];
+
+/// A family of weapons that fly the same way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Weapon {
+    /// A single shell, which explodes where it first hits the ground
+    #[default]
+    Shot,
+    /// Several shells fired at once, `spread` degrees apart around the aimed angle
+    Spread { shells: u8, spread: i8 },
+    /// A single shell that the wind affects less
+    Heavy,
+    /// Bounces off the ground `bounces` times, exploding where it lands after the last
+    Bouncer { bounces: u8 },
+    /// Rolls along the ground after landing, until it slows to a stop or the ground rises in front of it
+    Roller,
+    /// Splits into several shells at the top of its arc, which spread out horizontally
+    Splitter { shells: u8 },
+    /// Steers towards the nearest target once it starts falling
+    Homing
+}
+
+/// The path of a single projectile.
+///
+/// A projectile that wraps around the border has a separate path for each time it crosses the screen.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProjectilePath {
+    /// Relative to bottom left
+    pub points: Vec<Coordinate<i32>>,
+    /// Where the projectile explodes, if it does before leaving the game area. Also the last point.
+    pub impact: Option<Coordinate<i32>>,
+    /// How long after firing the projectile reaches the last point, in the same units of time as `Tank::curve_function`.
+    /// Rolling is taken to be a unit of time for each column rolled over.
+    pub end_time: f32
+}
+
+impl ProjectilePath {
+    fn starting_at(start: Coordinate<i32>, time: f32) -> Self {
+        Self { points: vec![start], impact: None, end_time: time }
+    }
+
+    fn last(&self) -> Coordinate<i32> {
+        *self.points.last().expect("paths always have a start")
+    }
+
+    /// A path through `points`, exploding at the last one if `impact`.
+    #[cfg(test)]
+    pub(crate) fn through(points: &[(i32, i32)], impact: bool, end_time: f32) -> Self {
+        let points: Vec<_> = points.iter().map(|(x, y)| Coordinate(*x, *y)).collect();
+        Self { impact: impact.then(|| *points.last().unwrap()), points, end_time }
+    }
+}
+
+/// The flight of a projectile until it lands, leaves the game area or runs out of time.
+struct Flight {
+    paths: Vec<ProjectilePath>,
+    /// The motion since the projectile last bounced off or wrapped around the border
+    ballistic: Ballistic,
+    /// How long after firing `ballistic` started
+    started: f32,
+    /// How long after the start of `ballistic` the flight ended
+    time: f32,
+    /// Whether the flight ended because it ran out of time, rather than landing or leaving
+    timed_out: bool
+}
+
+impl Flight {
+    fn impact(&self) -> Option<Coordinate<i32>> {
+        self.paths.last().and_then(|path| path.impact)
+    }
+
+    fn last(&self) -> Coordinate<i32> {
+        self.paths.last().expect("flights always have a path").last()
+    }
+
+    fn velocity(&self) -> (f32, f32) {
+        self.ballistic.velocity_at(self.time)
+    }
+
+    fn end_time(&self) -> f32 {
+        self.started + self.time
+    }
+}
+
+/// Where a projectile at `point`, beyond `edge`, comes back into `area` when it bounces off or wraps around the border.
+fn reenter(point: Coordinate<i32>, edge: Edge, area: Rect, border: Border) -> Coordinate<i32> {
+    let (min_x, max_x, max_y) = (area.origin.0 as i32 + 1, area.end().0 as i32 - 1, area.end().1 as i32 - 1);
+
+    match (border, edge) {
+        (Border::Wrap, Edge::Left) => Coordinate(max_x, point.1),
+        (Border::Wrap, Edge::Right) => Coordinate(min_x, point.1),
+        _ => Coordinate(point.0.clamp(min_x, max_x), point.1.min(max_y))
+    }
+}
+
+/// Flies `ballistic`, fired at `start_time`, for `duration`.
+/// It is stopped where it hits `terrain` or leaves `area`, and bounces off or wraps around `border`.
+/// A projectile still flying after `MAX_FLIGHT_STEPS` is stopped without an impact.
+fn fly(ballistic: &Ballistic, area: Rect, border: Border, terrain: Option<&Terrain>, start_time: f32, duration: f32) -> Flight {
+    let mut paths = vec![ProjectilePath::starting_at(ballistic.start, start_time)];
+    let mut ballistic = *ballistic;
+    let mut started = start_time;
+    let mut border_hits = 0;
+    let mut steps = 0;
+    let mut t = 0.0;
+
+    let finish = |mut paths: Vec<ProjectilePath>, ballistic, started, time, timed_out| {
+        last_path(&mut paths).end_time = started + time;
+        Flight { paths, ballistic, started, time, timed_out }
+    };
+
+    loop {
+        if started + t + 1.0 > start_time + duration {
+            return finish(paths, ballistic, started, t, true)
+        }
+        if steps == MAX_FLIGHT_STEPS {
+            return finish(paths, ballistic, started, t, false)
+        }
+        steps += 1;
+        t += 1.0;
+
+        let path = last_path(&mut paths);
+        let current = ballistic.position(t);
+
+        if let Some(edge) = Edge::crossed(current, area, border) {
+            if edge == Edge::Bottom || border == Border::Stop || border_hits == MAX_BORDER_HITS {
+                return finish(paths, ballistic, started, t - 1.0, false)
+            }
+            border_hits += 1;
+
+            let crossing = ballistic.crossing_time(edge, area, t - 1.0, t);
+            let start = reenter(ballistic.position(crossing), edge, area, border);
+            let (vx, vy) = ballistic.velocity_at(crossing);
+
+            let velocity = match (border, edge) {
+                (Border::Bounce, Edge::Left | Edge::Right) => (-vx, vy),
+                (Border::Bounce, _) => (vx, -vy),
+                _ => (vx, vy)
+            };
+
+            started += crossing;
+
+            // wrapping jumps across the screen, so starts a new path
+            if border == Border::Wrap {
+                path.end_time = started;
+                paths.push(ProjectilePath::starting_at(start, started));
+            } else {
+                path.points.push(start);
+            }
+
+            ballistic = Ballistic { start, velocity, ..ballistic };
+            t = 0.0;
+            continue
+        }
+
+        if let Some(impact) = terrain.and_then(|terrain| terrain.segment_impact(path.last(), current)) {
+            path.points.push(impact);
+            path.impact = Some(impact);
+            return finish(paths, ballistic, started, t - 0.5, false)
+        }
+
+        path.points.push(current);
+    }
+}
+
+/// Flies until the projectile stops rising.
+fn fly_to_apex(ballistic: &Ballistic, area: Rect, border: Border, terrain: Option<&Terrain>) -> Flight {
+    fly(ballistic, area, border, terrain, 0.0, ballistic.apex_time().floor().max(0.0))
+}
+
+/// Continues `paths` with `flight`, which starts where they end.
+fn extend(paths: &mut Vec<ProjectilePath>, flight: Flight) {
+    let mut flight_paths = flight.paths.into_iter();
+
+    if let Some(last) = paths.last_mut() {
+        let first = flight_paths.next().expect("flights always have a path");
+        last.points.extend(first.points);
+        last.impact = first.impact;
+        last.end_time = first.end_time;
+    }
+    paths.extend(flight_paths);
+}
+
+fn last_path(paths: &mut [ProjectilePath]) -> &mut ProjectilePath {
+    paths.last_mut().expect("weapons always fire at least one path")
+}
+
+fn speed(velocity: (f32, f32)) -> f32 {
+    velocity.0.hypot(velocity.1)
+}
+
+impl Weapon {
+    /// Find a weapon in the `CATALOGUE` by name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        CATALOGUE.iter()
+            .find(|(weapon_name, _)| weapon_name.eq_ignore_ascii_case(name))
+            .map(|(_, weapon)| *weapon)
+    }
+
+    /// How strongly the wind affects the projectile, compared to a plain shot.
+    ///
+    /// Approximate for heavy, like the constants above.
+    pub fn wind_multiplier(&self) -> f32 {
+        match self {
+            Weapon::Heavy => 0.5,
+            _ => 1.0
+        }
+    }
+
+    /// The radius of the explosion where each projectile lands, in pixels on a 2560x1440 monitor.
+    ///
+    /// Approximate, like the constants above.
+    pub fn blast_radius(&self) -> f32 {
+        match self {
+            Weapon::Shot | Weapon::Roller => 40.0,
+            Weapon::Spread { .. } | Weapon::Splitter { .. } => 28.0,
+            Weapon::Heavy => 70.0,
+            Weapon::Bouncer { .. } | Weapon::Homing => 35.0
+        }
+    }
+
+    /// Whether the path depends on where the targets are.
+    pub fn needs_targets(&self) -> bool {
+        matches!(self, Weapon::Homing)
+    }
+
+    /// The paths of every projectile fired by `tank`, stopped where they explode or leave the game area.
+    /// Projectiles bounce off or wrap around the edges of the game area according to the tank's `Border`.
+    ///
+    /// Without `terrain`, projectiles never land, so only fly until they leave the game area.
+    /// `targets` are the tanks a homing projectile can steer towards.
+    pub fn paths(&self, tank: &Tank, viewport: &Viewport, terrain: Option<&Terrain>, targets: &[Coordinate<u32>]) -> Vec<ProjectilePath> {
+        let (area, border) = (viewport.area, tank.border);
+        let launch = tank.launch(viewport);
+
+        match *self {
+            Weapon::Shot | Weapon::Heavy => fly(&launch, area, border, terrain, 0.0, f32::INFINITY).paths,
+            Weapon::Spread { shells, spread } => (0..shells as i16).flat_map(|shell| {
+                let offset = (shell - (shells as i16 - 1) / 2) * spread as i16;
+                let shell_tank = Tank { angle: (tank.angle as i16 + offset).clamp(i8::MIN as i16, i8::MAX as i16) as i8, ..tank.clone() };
+
+                fly(&shell_tank.launch(viewport), area, border, terrain, 0.0, f32::INFINITY).paths
+            }).collect(),
+            Weapon::Bouncer { bounces } => bounce(launch, area, border, terrain, bounces, viewport.scale().0),
+            Weapon::Roller => roll(launch, area, border, terrain),
+            Weapon::Splitter { shells } => split(launch, area, border, terrain, shells, viewport.scale().0),
+            Weapon::Homing => home(launch, area, border, terrain, targets, viewport.scale().0)
+        }
+    }
+}
+
+fn bounce(mut ballistic: Ballistic, area: Rect, border: Border, terrain: Option<&Terrain>, bounces: u8, scale: f32) -> Vec<ProjectilePath> {
+    let mut paths = Vec::new();
+    let mut time = 0.0;
+
+    for bounce in 0..=bounces {
+        let flight = fly(&ballistic, area, border, terrain, time, f32::INFINITY);
+        time = flight.end_time();
+        let velocity = flight.velocity();
+        let flight_ballistic = flight.ballistic;
+        let impact = flight.impact();
+        extend(&mut paths, flight);
+
+        let (Some(impact), Some(terrain)) = (impact, terrain) else { break };
+        if bounce == bounces {
+            break
+        }
+
+        // reflect off the ground, between the last two points before the impact
+        let normal = terrain.normal(impact.0);
+        let along = (velocity.0*normal.0 + velocity.1*normal.1).min(0.0);
+        let reflected = (
+            (velocity.0 - 2.0*along*normal.0) * BOUNCE_RESTITUTION,
+            (velocity.1 - 2.0*along*normal.1) * BOUNCE_RESTITUTION
+        );
+
+        if speed(reflected) < MIN_BOUNCE_SPEED * scale {
+            break
+        }
+
+        ballistic = Ballistic { start: Coordinate(impact.0, impact.1 + 1), velocity: reflected, ..flight_ballistic };
+    }
+
+    paths
+}
+
+fn roll(ballistic: Ballistic, area: Rect, border: Border, terrain: Option<&Terrain>) -> Vec<ProjectilePath> {
+    let flight = fly(&ballistic, area, border, terrain, 0.0, f32::INFINITY);
+    let (Some(mut current), Some(terrain)) = (flight.impact(), terrain) else { return flight.paths };
+
+    // keep rolling the way it was travelling, or downhill if it landed vertically
+    let velocity = flight.velocity();
+    let gravity = -flight.ballistic.acceleration.1;
+    let mut speed_squared = velocity.0.powi(2);
+    let mut step = if velocity.0 != 0.0 { velocity.0.signum() } else { terrain.normal(current.0).0.signum() } as i32;
+    let mut paths = flight.paths;
+    if step == 0 {
+        return paths
+    }
+
+    // it can't roll further than across the screen and back
+    for _ in 0..2*area.size.0 {
+        let mut x = current.0 + step;
+        let edge = if x < area.origin.0 as i32 {
+            Some(Edge::Left)
+        } else if x >= area.end().0 as i32 {
+            Some(Edge::Right)
+        } else {
+            None
+        };
+
+        if let Some(edge) = edge {
+            match border {
+                Border::Stop => {
+                    last_path(&mut paths).impact = None;
+                    return paths
+                },
+                Border::Bounce => {
+                    step = -step;
+                    continue
+                },
+                Border::Wrap => x = reenter(Coordinate(x, current.1), edge, area, border).0
+            }
+        }
+
+        match terrain.ground_height(x) {
+            // rolled into a gap
+            None => {
+                last_path(&mut paths).impact = None;
+                return paths
+            },
+            Some(ground) if ground as i32 > current.1 => break,
+            Some(ground) => {
+                // rolling downhill speeds it up, and friction slows it for every column rolled over
+                speed_squared += 2.0 * gravity * ((current.1 - ground as i32) as f32 - ROLL_FRICTION);
+                if speed_squared <= 0.0 {
+                    break
+                }
+
+                current = Coordinate(x, ground as i32);
+                let time = last_path(&mut paths).end_time + 1.0;
+                if edge.is_some() {
+                    paths.push(ProjectilePath::starting_at(current, time));
+                } else {
+                    let path = last_path(&mut paths);
+                    path.points.push(current);
+                    path.end_time = time;
+                }
+            }
+        }
+    }
+
+    last_path(&mut paths).impact = Some(current);
+    paths
+}
+
+fn split(ballistic: Ballistic, area: Rect, border: Border, terrain: Option<&Terrain>, shells: u8, scale: f32) -> Vec<ProjectilePath> {
+    let flight = fly_to_apex(&ballistic, area, border, terrain);
+    if !flight.timed_out {
+        return flight.paths
+    }
+
+    let start = flight.last();
+    let velocity = flight.velocity();
+    let split_time = flight.end_time();
+    let split_ballistic = flight.ballistic;
+
+    let mut paths = flight.paths;
+    for shell in 0..shells as i16 {
+        let offset = (shell - (shells as i16 - 1) / 2) as f32 * SPLIT_SPEED * scale;
+        let shell = Ballistic { start, velocity: (velocity.0 + offset, velocity.1), ..split_ballistic };
+
+        paths.extend(fly(&shell, area, border, terrain, split_time, f32::INFINITY).paths);
+    }
+
+    paths
+}
+
+fn home(ballistic: Ballistic, area: Rect, border: Border, terrain: Option<&Terrain>, targets: &[Coordinate<u32>], scale: f32) -> Vec<ProjectilePath> {
+    let flight = fly_to_apex(&ballistic, area, border, terrain);
+    let apex = flight.last();
+
+    let nearest = targets.iter()
+        .map(|target| Coordinate(target.0 as i32, target.1 as i32))
+        .min_by_key(|target| (target.0 - apex.0).pow(2) + (target.1 - apex.1).pow(2));
+    let Some(target) = nearest else {
+        // nothing to home in on, so it falls like a plain shot
+        return fly(&ballistic, area, border, terrain, 0.0, f32::INFINITY).paths
+    };
+    if !flight.timed_out {
+        return flight.paths
+    }
+
+    let velocity = flight.velocity();
+    let speed = speed(velocity).max(MIN_HOMING_SPEED * scale);
+    let mut heading = velocity.1.atan2(velocity.0);
+    let mut position = (apex.0 as f32, apex.1 as f32);
+    let mut paths = flight.paths;
+    let mut border_hits = 0;
+
+    for _ in 0..MAX_HOMING_STEPS {
+        let path = last_path(&mut paths);
+        path.end_time += 1.0;
+
+        let to_target = (target.0 as f32 - position.0, target.1 as f32 - position.1);
+        if to_target.0.hypot(to_target.1) <= speed {
+            path.points.push(target);
+            path.impact = Some(target);
+            break
+        }
+
+        let turn = ((to_target.1.atan2(to_target.0) - heading + PI).rem_euclid(TAU) - PI).clamp(-HOMING_TURN, HOMING_TURN);
+        heading += turn;
+        position = (position.0 + speed * heading.cos(), position.1 + speed * heading.sin());
+
+        let current = Coordinate(position.0 as i32, position.1 as i32);
+        if let Some(edge) = Edge::crossed(current, area, border) {
+            if edge == Edge::Bottom || border == Border::Stop || border_hits == MAX_BORDER_HITS {
+                break
+            }
+            border_hits += 1;
+
+            let start = reenter(current, edge, area, border);
+            match (border, edge) {
+                (Border::Bounce, Edge::Left | Edge::Right) => heading = PI - heading,
+                (Border::Bounce, _) => heading = -heading,
+                _ => {}
+            }
+            if border == Border::Wrap {
+                let time = path.end_time;
+                paths.push(ProjectilePath::starting_at(start, time));
+            } else {
+                path.points.push(start);
+            }
+
+            position = (start.0 as f32, start.1 as f32);
+            continue
+        }
+
+        if let Some(impact) = terrain.and_then(|terrain| terrain.segment_impact(path.last(), current)) {
+            path.points.push(impact);
+            path.impact = Some(impact);
+            break
+        }
+
+        path.points.push(current);
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Size;
+    use crate::viewport::Layout;
+    use crate::tank::{Direction, Physics};
+
+    const DIMENSIONS: Size<u32> = Size(2560, 1440);
+
+    fn viewport() -> Viewport {
+        Viewport::from_dimensions(DIMENSIONS, Layout::default())
+    }
+
+    fn tank(weapon: Weapon) -> Tank {
+        Tank { screen_position: Coordinate(300, 400), weapon, border: Border::Stop, ..Tank::new(50, 60, 0, Direction::Right) }
+    }
+
+    fn fire(tank: Tank, terrain: &Terrain, targets: &[Coordinate<u32>]) -> Vec<ProjectilePath> {
+        tank.weapon.paths(&tank, &viewport(), Some(terrain), targets)
+    }
+
+    fn flat(height: u32) -> Terrain {
+        Terrain::from_heights(vec![Some(height); DIMENSIONS.0 as usize])
+    }
+
+    #[test]
+    fn catalogue_test() {
+        assert_eq!(Weapon::from_name("Roller"), Some(Weapon::Roller));
+        assert_eq!(Weapon::from_name("laser"), None);
+
+        let terrain = flat(300);
+        let shot = fire(tank(Weapon::Shot), &terrain, &[]);
+        assert_eq!(shot.len(), 1);
+        assert_eq!(shot[0].impact.unwrap().1, 300);
+
+        // the middle shell of a spread is the aimed shot, and the lower shell flies further from this high angle
+        let spread = fire(tank(Weapon::Spread { shells: 3, spread: 5 }), &terrain, &[]);
+        assert_eq!(spread.len(), 3);
+        assert_eq!(spread[1], shot[0]);
+        assert!(spread[0].impact.unwrap().0 > spread[2].impact.unwrap().0);
+
+        // the heavy shell drifts less in the wind
+        let windy = |weapon| fire(Tank { wind: 50, ..tank(weapon) }, &terrain, &[])[0].impact.unwrap().0;
+        assert!(windy(Weapon::Heavy) < windy(Weapon::Shot));
+        assert!(windy(Weapon::Heavy) > shot[0].impact.unwrap().0);
+    }
+
+    #[test]
+    fn bouncer_and_roller_test() {
+        let terrain = flat(300);
+        let shot = fire(tank(Weapon::Shot), &terrain, &[]).remove(0);
+        let bouncer = fire(tank(Weapon::Bouncer { bounces: 2 }), &terrain, &[]).remove(0);
+
+        // the bouncer lands where the shot does, then carries on in smaller hops
+        assert!(bouncer.points.starts_with(&shot.points));
+        let bounced = &bouncer.points[shot.points.len()..];
+        assert!(bounced.iter().all(|point| point.1 >= 300 && point.1 < shot.points.iter().map(|point| point.1).max().unwrap()));
+        assert!(bouncer.impact.unwrap().0 > shot.impact.unwrap().0);
+
+        // the roller rolls down the slope it lands on, and stops in the dip
+        let valley = shot.impact.unwrap().0 + 40;
+        let terrain = Terrain::from_heights((0..DIMENSIONS.0 as i32).map(|x| Some((300 + (x - valley).abs()) as u32)).collect());
+        let roller = fire(tank(Weapon::Roller), &terrain, &[]).remove(0);
+        assert_eq!(roller.impact, Some(Coordinate(valley, 300)));
+
+        // on flat ground it slows to a stop, well before the edge of the screen
+        let terrain = flat(300);
+        let roller = fire(tank(Weapon::Roller), &terrain, &[]).remove(0);
+        let (landed, stopped) = (shot.impact.unwrap(), roller.impact.unwrap());
+        assert_eq!(stopped.1, 300);
+        assert!(stopped.0 > landed.0 + 100 && stopped.0 < 2000, "{landed:?} {stopped:?}");
+    }
+
+    #[test]
+    fn splitter_and_homing_test() {
+        let terrain = flat(300);
+        let shot = fire(tank(Weapon::Shot), &terrain, &[]).remove(0);
+
+        let splitter = fire(tank(Weapon::Splitter { shells: 3 }), &terrain, &[]);
+        assert_eq!(splitter.len(), 4);
+        assert_eq!(splitter[0].impact, None);
+        let apex = splitter[0].points.last().unwrap();
+        assert!(splitter[1..].iter().all(|shell| shell.points[0] == *apex && shell.impact.is_some()));
+        // the middle shell carries on like the shot, give or take restarting from a whole pixel
+        assert!((splitter[2].impact.unwrap().0 - shot.impact.unwrap().0).abs() <= 2);
+
+        // the homing shell steers onto a target well short of where the shot lands
+        let target = Coordinate(shot.impact.unwrap().0 as u32 - 200, 310);
+        let homing = fire(tank(Weapon::Homing), &terrain, &[target, Coordinate(100, 310)]).remove(0);
+        assert_eq!(homing.impact, Some(Coordinate(target.0 as i32, 310)));
+
+        // without targets, it flies like a plain shot
+        assert_eq!(fire(tank(Weapon::Homing), &terrain, &[]).remove(0), shot);
+    }
+
+    #[test]
+    fn border_test() {
+        let terrain = flat(300);
+        let near_wall = |border| Tank { screen_position: Coordinate(2300, 400), border, ..tank(Weapon::Shot) };
+
+        // the shot would land off the right of the screen
+        let stopped = fire(near_wall(Border::Stop), &terrain, &[]);
+        assert_eq!(stopped.len(), 1);
+        assert_eq!(stopped[0].impact, None);
+
+        let bounced = fire(near_wall(Border::Bounce), &terrain, &[]);
+        assert_eq!(bounced.len(), 1);
+        let impact = bounced[0].impact.unwrap();
+        assert!(impact.0 < 2560 && impact.1 == 300);
+        assert!(bounced[0].points.iter().all(|point| (1..2560).contains(&point.0)));
+
+        // wrapping comes back in on the left, where it lands as far past the edge as the bounce lands before it
+        let wrapped = fire(near_wall(Border::Wrap), &terrain, &[]);
+        assert_eq!(wrapped.len(), 2);
+        assert_eq!(wrapped[0].impact, None);
+        assert_eq!(wrapped[1].points[0].0, 1);
+        assert!((wrapped[1].impact.unwrap().0 - (2560 - impact.0)).abs() <= 4);
+
+        // a steep shot bounces off the top of the screen, so lands sooner
+        let steep = |border| fire(Tank { power: 100, angle: 80, border, ..tank(Weapon::Shot) }, &terrain, &[]).remove(0);
+        let (open, ceiling) = (steep(Border::Stop), steep(Border::Bounce));
+        assert!(open.points.iter().any(|point| point.1 >= 1440));
+        assert!(ceiling.points.iter().all(|point| point.1 < 1440));
+        assert!(ceiling.impact.unwrap().0 < open.impact.unwrap().0);
+    }
+
+    #[test]
+    fn endless_flight_test() {
+        // without gravity or power the shell hangs in the air forever
+        let still = Tank { power: 0, physics: Physics { wind_constant: 0.0, path_constant: 0.0 }, ..tank(Weapon::Shot) };
+
+        for weapon in [Weapon::Shot, Weapon::Roller, Weapon::Bouncer { bounces: 2 }, Weapon::Splitter { shells: 3 }, Weapon::Homing] {
+            let paths = fire(Tank { weapon, ..still.clone() }, &flat(300), &[]);
+            assert!(paths.iter().all(|path| path.impact.is_none()), "{weapon:?}");
+        }
+        assert_eq!(still.curve_points(&viewport()).len(), MAX_FLIGHT_STEPS + 1);
+        assert!(still.trajectory(&viewport(), Some(&flat(300)), &[]).impact().is_none());
+    }
+}
@@ -8,22 +8,28 @@ use thiserror::Error;
 
 use winapi::ctypes::c_void;
 use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
-use winapi::shared::windef::{HWND, HBITMAP, RECT, POINT, SIZE, HDC, HPEN, HBRUSH};
+use winapi::shared::windef::{HWND, HBITMAP, RECT, POINT, SIZE, HDC, HBRUSH};
 use winapi::um::errhandlingapi::GetLastError;
 use winapi::um::libloaderapi::GetModuleHandleW;
 use winapi::um::wingdi::{
     CreateSolidBrush, CreateCompatibleDC, BITMAPINFOHEADER, BI_RGB, BITMAPINFO, RGBQUAD, SelectObject, BLENDFUNCTION,
-    AC_SRC_OVER, AC_SRC_ALPHA, DeleteObject, DeleteDC, GdiFlush, MoveToEx, LineTo, HGDI_ERROR, CreatePen, PS_SOLID, CreateCompatibleBitmap, GetDIBits, DIB_RGB_COLORS, GetStockObject, BLACK_BRUSH
+    AC_SRC_OVER, AC_SRC_ALPHA, DeleteObject, DeleteDC, GdiFlush, HGDI_ERROR, CreateCompatibleBitmap, GetDIBits, SetDIBits, DIB_RGB_COLORS, GetStockObject, BLACK_BRUSH
 };
 use winapi::um::winuser::{
     CreateWindowExW, DefWindowProcW, LoadCursorW, RegisterClassExW, ShowWindow, WNDCLASSEXW, CS_HREDRAW, CS_VREDRAW, WM_DESTROY, IDC_ARROW, SW_SHOW,
     CW_USEDEFAULT, WS_EX_LAYERED, WS_EX_TRANSPARENT, WS_EX_TOPMOST, WS_MAXIMIZE, EnumWindows, GetWindowTextW, PostQuitMessage, UpdateLayeredWindow,
-    GetDC, ULW_ALPHA, ReleaseDC, PrintWindow, PW_RENDERFULLCONTENT, OpenClipboard, SetClipboardData, EmptyClipboard, CloseClipboard, CF_BITMAP, FillRect, GetWindowRect
+    GetDC, ULW_ALPHA, ReleaseDC, PrintWindow, PW_RENDERFULLCONTENT, OpenClipboard, SetClipboardData, EmptyClipboard, CloseClipboard, CF_BITMAP, FillRect, GetWindowRect,
+    MSG, TranslateMessage, DispatchMessageW, PeekMessageW, PM_REMOVE, WM_QUIT
 };
 
-use crate::tank::Tank;
+use crate::backend::{FrameSource, OverlaySink};
+use crate::draw::{self, TrajectoryStyle};
+use crate::font::{draw_text, TextStyle};
+use crate::impact::ImpactMarker;
+use crate::tracking::TrackingStatus;
+use crate::trajectory::Trajectory;
 use crate::{Coordinate, Size};
-use crate::bitmap::ARGB;
+use crate::bitmap::{Bitmap, BitmapBuf, ARGB};
 
 // ###############################
 // ############ Misc #############
@@ -59,43 +65,6 @@ impl From<u32> for WindowsError {
     }
 }
 
-/// Run a basic message loop for a given window handle
-#[macro_export]
-macro_rules! WindowsMessageLoop {
-    ($handle: ident, $loop_time: ident $(,$inner: tt)?) => {
-        use std::thread;
-        use std::ptr::null_mut;
-        use winapi::um::winuser::{
-            MSG, TranslateMessage, DispatchMessageW, PeekMessageW, PM_REMOVE, WM_QUIT
-        };
-        use winapi::shared::windef::POINT;
-
-        let mut msg = MSG {
-            hwnd: null_mut(),
-            message: 0,
-            wParam: 0,
-            lParam: 0,
-            time: 0,
-            pt: POINT {x: 0, y: 0},
-        };
-
-        unsafe {
-            'outer: loop {
-                while PeekMessageW(&mut msg, null_mut(), 0, 0, PM_REMOVE) != 0 {
-                    TranslateMessage(&msg);
-                    DispatchMessageW(&msg);
-                    if msg.message == WM_QUIT {
-                        break 'outer;
-                    }
-                }
-                $($inner)?
-                thread::sleep($loop_time);
-            }
-        }
-    };
-}
-
-
 fn to_wstring(s: &str) -> Vec<u16> {
     OsStr::new(s)
         .encode_wide()
@@ -272,21 +241,6 @@ unsafe fn draw_cleanup(hwnd: HWND, hdc: HDC, mem_hdc: HDC, old: *mut c_void) ->
     return_result
 }
 
-pub fn create_pen(width: u32, color: ARGB) -> Result<HPEN, WindowsError> {
-    // safe as not using any pointers as arguments
-    
-    let pen = unsafe {
-        CreatePen(PS_SOLID as i32, width as i32, color.as_colorref())
-    };
-
-    if pen.is_null() {
-        let code = unsafe { GetLastError() };
-        Err(WindowsError { code, error_type: WindowsErrorType::CreateObject })
-    } else {
-        Ok(pen)
-    }
-}
-
 pub fn create_bitmap_header(dimensions: Size<u32>) -> BITMAPINFOHEADER {
     BITMAPINFOHEADER {
         biSize: size_of::<BITMAPINFOHEADER>() as u32,
@@ -351,33 +305,6 @@ pub unsafe fn create_dibitmap(hwnd: HWND, dimensions: Size<u32>, color: ARGB) ->
     Ok(bitmap)
 }
 
-pub unsafe fn clear_bitmap(hwnd: HWND, dibitmap: HBITMAP, dimensions: Size<u32>) -> Result<(), WindowsError> {
-    let (hdc, mem_hdc) = create_mem_dc(hwnd)?;
-
-    let old = SelectObject(mem_hdc, dibitmap as *mut c_void);
-    if old.is_null() {
-        DeleteDC(mem_hdc);
-        ReleaseDC(hwnd, hdc);
-        return Err(WindowsError { code: GetLastError(), error_type: WindowsErrorType::SelectObject });
-    }
-
-    let solid = CreateSolidBrush(0);
-    if solid.is_null() {
-        let _ = draw_cleanup(hwnd, hdc, mem_hdc, old);
-        return Err(WindowsError { code: GetLastError(), error_type: WindowsErrorType::CreateObject })
-    }
-
-    let fill_result = FillRect(mem_hdc, &RECT {left: 0, top: 0, right: dimensions.0 as i32, bottom: dimensions.1 as i32}, solid);
-    let delete_result = DeleteObject(solid as *mut c_void);
-
-    draw_cleanup(hwnd, hdc, mem_hdc, old)?;
-
-    if fill_result == 0 { return Err(WindowsError { code: GetLastError(), error_type: WindowsErrorType::Other }) }
-    if delete_result == 0 { return Err(WindowsError { code: GetLastError(), error_type: WindowsErrorType::DeleteObject }) }
-
-    Ok(())
-}
-
 pub unsafe fn draw_bitmap(hwnd: HWND, dibitmap: HBITMAP, dimensions: Size<u32>) -> Result<(), WindowsError> {
     let (hdc, mem_hdc) = create_mem_dc(hwnd)?;
 
@@ -416,83 +343,6 @@ pub unsafe fn draw_bitmap(hwnd: HWND, dibitmap: HBITMAP, dimensions: Size<u32>)
     Ok(())
 }
 
-/// Coordinates relative to bottom-left
-/// Returned error is a windows error code. If there is an error in drawing and in cleanup, the error code is the cleanup error code.
-pub unsafe fn draw_line(hwnd: HWND, dibitmap: HBITMAP, dimensions: Size<u32>, pen: HPEN, from: Coordinate<i32>, to: Coordinate<i32>) -> Result<(), WindowsError> {
-    // add any offset
-    let from = (from.0, from.1);
-    let to = (to.0, to.1);
-
-    // coordinates are relative to bottom-left, so use height to flip it
-    let height = dimensions.1 as i32;
-
-    let (hdc, mem_hdc) = create_mem_dc(hwnd)?;
-
-    let old_bmap = SelectObject(mem_hdc, dibitmap as *mut c_void);
-    if old_bmap.is_null() {
-        DeleteDC(mem_hdc);
-        ReleaseDC(hwnd, hdc);
-        return Err(WindowsError { code: GetLastError(), error_type: WindowsErrorType::SelectObject })
-    }
-
-    let old_pen = SelectObject(mem_hdc, pen as *mut c_void);
-    if old_pen.is_null() {
-        let _ = draw_cleanup(hwnd, hdc, mem_hdc, old_bmap);
-        return Err(WindowsError { code: GetLastError(), error_type: WindowsErrorType::SelectObject })
-    }
-
-    let move_result = MoveToEx(mem_hdc, from.0, height-from.1, null_mut());
-    let line_result = LineTo(mem_hdc, to.0, height-to.1);
-    let select_result = SelectObject(mem_hdc, old_pen);
-
-    draw_cleanup(hwnd, hdc, mem_hdc, old_bmap)?;
-
-    if move_result == 0 || line_result == 0 {
-        return Err(GetLastError().into())
-    }
-
-    if select_result.is_null() {
-        return Err(WindowsError { code: GetLastError(), error_type: WindowsErrorType::SelectObject });
-    }
-
-    Ok(())
-}
-
-/// Uses a curve function that takes a parameter t, the distance along the line, and returns a (x, y) coordinate.
-/// The curve is stopped when x < 0 or x > max_x, or y > max_y.
-pub unsafe fn draw_tank_curve(hwnd: HWND, dibitmap: HBITMAP, dimensions: Size<u32>, pen: HPEN, tank: &Tank) -> Result<(), WindowsError> {
-    /// The length of dotted lines drawn.
-    const DOT_LENGTH: i32 = 4;
-
-    let mut solid_part = true;
-    let mut t = 0;
-    let mut temp_start = tank.curve_function(t, dimensions);
-
-    let (max_x, _max_y) = (dimensions.0 as i32, dimensions.1 as i32);
-
-    loop {
-        t += 1;
-        let current = tank.curve_function(t, dimensions);
-
-        if current.0 > max_x || current.0 <= 0 || current.1 <= 0 {
-            break
-        }
-
-        let square_sum = (current.0-temp_start.0).pow(2) + (current.1-temp_start.1).pow(2);
-        let current_line_length = (square_sum as f32).sqrt() as i32;
-
-        if current_line_length >= DOT_LENGTH {
-            if solid_part {
-                draw_line(hwnd, dibitmap, dimensions, pen, temp_start, current)?;
-            }
-            solid_part = !solid_part;
-            temp_start = current;
-        }
-    }
-
-    Ok(())
-}
-
 pub unsafe fn bitmap_to_clipboard(bitmap: HBITMAP) -> Result<(), WindowsError> {
     if OpenClipboard(std::ptr::null_mut()) == 0 {
         return Err(GetLastError().into())
@@ -527,9 +377,24 @@ pub unsafe fn bitmap_bits_to_buffer(hwnd: HWND, bitmap: HBITMAP, size: Size<u32>
     Ok(())
 }
 
-pub unsafe fn object_cleanup(bitmap: HBITMAP, pen: HPEN) {
+/// Copy `buffer`, with rows stored bottom-up, into the bitmap.
+pub unsafe fn buffer_to_bitmap_bits(hwnd: HWND, bitmap: HBITMAP, size: Size<u32>, buffer: *const ARGB) -> Result<(), WindowsError> {
+    let hdc = GetDC(hwnd);
+    let result_scanlines = SetDIBits(hdc, bitmap, 0, size.1, buffer as *const c_void, &create_bitmap_info(create_bitmap_header(size)), DIB_RGB_COLORS);
+
+    if ReleaseDC(hwnd, hdc) == 0 {
+        return Err(WindowsError { code: GetLastError(), error_type: WindowsErrorType::ReleaseDc })
+    }
+
+    if result_scanlines == 0 {
+        return Err(GetLastError().into())
+    }
+
+    Ok(())
+}
+
+pub unsafe fn object_cleanup(bitmap: HBITMAP) {
     DeleteObject(bitmap as *mut c_void);
-    DeleteObject(pen as *mut c_void);
 }
 
 // ###################################
@@ -567,3 +432,113 @@ pub unsafe fn screen_capture(hwnd: HWND) -> Result<HBITMAP, WindowsError> {
 
     Ok(bitmap)
 }
+
+// ###################################
+// ############ Backend ##############
+// ###################################
+
+/// The object handles must be exclusive pointers as they are deleted after use.
+pub struct WindowsObjects {
+    /// The bitmap presented to the layered window
+    pub bitmap: HBITMAP
+}
+
+impl Drop for WindowsObjects {
+    fn drop(&mut self) {
+        unsafe { object_cleanup(self.bitmap) }
+    }
+}
+
+/// Captures frames from the ShellShock window.
+pub struct WindowsFrameSource {
+    pub shellshock_handle: HWND,
+    /// The size of the last capture, used if the game window can't be measured
+    pub dimensions: Size<u32>
+}
+
+impl FrameSource for WindowsFrameSource {
+    type Error = WindowsError;
+
+    fn dimensions(&self) -> Size<u32> {
+        unsafe { window_dimensions(self.shellshock_handle) }.unwrap_or(self.dimensions)
+    }
+
+    fn capture(&mut self, buffer: &mut Bitmap<ARGB>) -> Result<(), WindowsError> {
+        // The buffer was sized from `dimensions`, so copy exactly that much
+        self.dimensions = buffer.dimensions();
+        unsafe {
+            let screen_cap = screen_capture(self.shellshock_handle)?;
+            let result = bitmap_bits_to_buffer(self.shellshock_handle, screen_cap, self.dimensions, buffer.inner.as_mut_ptr());
+            DeleteObject(screen_cap as *mut c_void);
+            result
+        }
+    }
+}
+
+/// Draws the overlay onto our own layered window.
+pub struct WindowsOverlay {
+    pub window_handle: HWND,
+    pub dimensions: Size<u32>,
+    pub windows_objects: WindowsObjects,
+    /// Each frame is drawn here, then copied to `windows_objects.bitmap` once when presented
+    pub frame: BitmapBuf<ARGB>
+}
+
+impl OverlaySink for WindowsOverlay {
+    type Error = WindowsError;
+
+    fn draw_trajectory(&mut self, trajectory: &Trajectory, style: TrajectoryStyle) -> Result<(), WindowsError> {
+        draw::draw_trajectory(&mut self.frame.as_bitmap(), trajectory, style);
+        Ok(())
+    }
+
+    fn draw_impact(&mut self, marker: &ImpactMarker) -> Result<(), WindowsError> {
+        draw::draw_impact(&mut self.frame.as_bitmap(), marker);
+        Ok(())
+    }
+
+    fn draw_text(&mut self, text: &str, position: Coordinate<i32>, style: TextStyle) -> Result<(), WindowsError> {
+        draw_text(&mut self.frame.as_bitmap(), text, position, style);
+        Ok(())
+    }
+
+    fn draw_status(&mut self, status: &TrackingStatus) -> Result<(), WindowsError> {
+        draw::draw_status(&mut self.frame.as_bitmap(), status);
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), WindowsError> {
+        unsafe {
+            buffer_to_bitmap_bits(self.window_handle, self.windows_objects.bitmap, self.dimensions, self.frame.pixels().as_ptr())?;
+            draw_bitmap(self.window_handle, self.windows_objects.bitmap, self.dimensions)
+        }
+    }
+
+    fn clear(&mut self) -> Result<(), WindowsError> {
+        self.frame.as_bitmap().fill(0.into());
+        Ok(())
+    }
+
+    fn handle_events(&mut self) -> bool {
+        let mut msg = MSG {
+            hwnd: null_mut(),
+            message: 0,
+            wParam: 0,
+            lParam: 0,
+            time: 0,
+            pt: POINT {x: 0, y: 0},
+        };
+
+        unsafe {
+            while PeekMessageW(&mut msg, null_mut(), 0, 0, PM_REMOVE) != 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+                if msg.message == WM_QUIT {
+                    return false
+                }
+            }
+        }
+
+        true
+    }
+}
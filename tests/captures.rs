@@ -0,0 +1,130 @@
+//! Checks the HUD and terrain are read correctly from captures of the game, see `captures/README.md`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use shellshock_tracer::{Coordinate, Rect, Size};
+use shellshock_tracer::bitmap::BitmapBuf;
+use shellshock_tracer::hud::{read_angle, read_power, read_wind, GlyphSet, Wind};
+use shellshock_tracer::tank::Direction;
+use shellshock_tracer::terrain::Terrain;
+use shellshock_tracer::viewport::{Layout, Viewport};
+
+const CAPTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/captures");
+/// How far the ground read can be from the expected height, in pixels.
+const GROUND_TOLERANCE: u32 = 2;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expected {
+    wind: Option<ExpectedWind>,
+    power: Option<u8>,
+    angle: Option<i8>,
+    terrain: Option<ExpectedTerrain>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedWind {
+    strength: u8,
+    direction: Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedTerrain {
+    ground: Vec<(usize, u32)>
+}
+
+/// Every capture with a list of what should be read from it.
+fn captures() -> Vec<(PathBuf, Expected)> {
+    let mut captures: Vec<_> = fs::read_dir(CAPTURES).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .map(|expected_path| {
+            let capture = ["png", "bmp", "ppm"].iter()
+                .map(|extension| expected_path.with_extension(extension))
+                .find(|path| path.exists())
+                .unwrap_or_else(|| panic!("no capture for {}", expected_path.display()));
+            let expected = toml::from_str(&fs::read_to_string(&expected_path).unwrap())
+                .unwrap_or_else(|error| panic!("{}: {error}", expected_path.display()));
+
+            (capture, expected)
+        })
+        .collect();
+
+    assert!(!captures.is_empty(), "no captures in {CAPTURES}, see its README.md");
+    captures.sort_by(|a, b| a.0.cmp(&b.0));
+    captures
+}
+
+/// The templates derived from the game's own characters, as the overlay's font would make a failure unclear.
+fn glyphs() -> GlyphSet {
+    let path = Path::new(CAPTURES).join("glyphs.png");
+    assert!(path.exists(), "no glyph sheet at {}, see the README.md next to it", path.display());
+
+    let mut sheet = BitmapBuf::load(path).unwrap();
+    let region = Rect { origin: Coordinate(0, 0), size: Size(sheet.width(), sheet.height()) };
+    GlyphSet::from_sheet(&sheet.as_bitmap(), region).unwrap()
+}
+
+fn direction(name: &str) -> Direction {
+    match name {
+        "left" => Direction::Left,
+        "right" => Direction::Right,
+        _ => panic!("unknown direction `{name}`")
+    }
+}
+
+#[test]
+#[ignore = "needs captures of the game in tests/captures, see its README.md"]
+fn read_wind_from_captures_test() {
+    let glyphs = glyphs();
+
+    for (path, expected) in captures() {
+        let Some(wind) = expected.wind else { continue };
+        let mut capture = BitmapBuf::load(&path).unwrap();
+        let viewport = Viewport::from_dimensions(capture.dimensions(), Layout::default());
+
+        let expected = Wind { strength: wind.strength, direction: wind.direction.as_deref().map(direction) };
+        assert_eq!(read_wind(&capture.as_bitmap(), &glyphs, &viewport), Ok(expected), "{}", path.display());
+    }
+}
+
+#[test]
+#[ignore = "needs captures of the game in tests/captures, see its README.md"]
+fn read_power_and_angle_from_captures_test() {
+    let glyphs = glyphs();
+
+    for (path, expected) in captures() {
+        let mut capture = BitmapBuf::load(&path).unwrap();
+        let viewport = Viewport::from_dimensions(capture.dimensions(), Layout::default());
+
+        if let Some(power) = expected.power {
+            assert_eq!(read_power(&capture.as_bitmap(), &glyphs, &viewport), Ok(power), "{}", path.display());
+        }
+        if let Some(angle) = expected.angle {
+            assert_eq!(read_angle(&capture.as_bitmap(), &glyphs, &viewport), Ok(angle), "{}", path.display());
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs captures of the game in tests/captures, see its README.md"]
+fn extract_terrain_from_captures_test() {
+    for (path, expected) in captures() {
+        let Some(terrain) = expected.terrain else { continue };
+        let mut capture = BitmapBuf::load(&path).unwrap();
+        let heights = Terrain::extract(&capture.as_bitmap(), Layout::default());
+
+        for (x, expected) in terrain.ground {
+            let height = heights.heights()[x];
+            assert!(
+                height.is_some_and(|height| height.abs_diff(expected) <= GROUND_TOLERANCE),
+                "{}: ground at column {x} is {height:?}, expected {expected}", path.display()
+            );
+        }
+    }
+}